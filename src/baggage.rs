@@ -0,0 +1,59 @@
+use crate::{formatting::is_core_key, otel::OtelVersion};
+use serde_json::Value;
+
+/// Selects which OpenTelemetry baggage entries are written as log attributes
+///
+/// The baggage is read from the OpenTelemetry context of the span an event belongs to,
+/// or from the active context if the event is outside of any span.
+///
+/// # Example
+/// ```
+/// use datadog_formatting_layer::{BaggageAttributes, DatadogFormattingLayer};
+///
+/// // writes e.g. "baggage.tenant.id": "acme"
+/// let layer = DatadogFormattingLayer::default()
+///     .with_baggage(BaggageAttributes::new(["tenant.id", "cohort"]));
+///
+/// // writes e.g. "tenant.id": "acme"
+/// let layer = DatadogFormattingLayer::default()
+///     .with_baggage(BaggageAttributes::new(["tenant.id"]).with_prefix(""));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BaggageAttributes {
+    allowed_keys: Vec<String>,
+    prefix: String,
+}
+
+impl BaggageAttributes {
+    /// Only write the baggage entries with the given keys, prefixed with `baggage.`
+    pub fn new<K: Into<String>>(allowed_keys: impl IntoIterator<Item = K>) -> Self {
+        Self {
+            allowed_keys: allowed_keys.into_iter().map(Into::into).collect(),
+            prefix: "baggage.".to_string(),
+        }
+    }
+
+    /// Use a custom prefix for the attribute names
+    ///
+    /// Entries which would overwrite the keys the layer writes itself (`timestamp`, `level`,
+    /// `message`, `target`, `dd.*` and `fields.*`) or the reserved attributes of Datadog
+    /// (`host`, `service`, `status`, `env`, `version`, `ddsource` and `ddtags`) are skipped.
+    #[must_use]
+    pub fn with_prefix(self, prefix: impl Into<String>) -> Self {
+        Self {
+            prefix: prefix.into(),
+            ..self
+        }
+    }
+
+    pub(crate) fn read<V: OtelVersion>(&self, otel_context: &V::Context) -> Vec<(String, Value)> {
+        self.allowed_keys
+            .iter()
+            .map(|key| (format!("{}{key}", self.prefix), key))
+            .filter(|(attribute, _)| !is_core_key(attribute))
+            .filter_map(|(attribute, key)| {
+                V::baggage(otel_context, key).map(|value| (attribute, value.into()))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,333 @@
+use crate::propagation::PropagatedState;
+use serde_json::Value;
+use std::sync::Arc;
+use tracing::{span::Attributes, Dispatch, Event, Id, Subscriber};
+use tracing_subscriber::{
+    layer::Context,
+    registry::{LookupSpan, SpanRef},
+};
+
+/// The trace and span id a log is correlated with
+///
+/// # Example
+/// ```
+/// use datadog_formatting_layer::CorrelationIds;
+///
+/// let ids = CorrelationIds::new(42, 7).with_sampled(false);
+///
+/// assert!(ids.is_valid());
+/// assert!(!ids.is_sampled());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CorrelationIds {
+    trace_id: u128,
+    span_id: u64,
+    sampling_priority: i8,
+    /// The state of an incoming request which is propagated to outgoing ones
+    propagated: Option<Arc<PropagatedState>>,
+}
+
+impl CorrelationIds {
+    /// Create sampled correlation ids from a 128-bit trace id and a 64-bit span id
+    #[must_use]
+    pub const fn new(trace_id: u128, span_id: u64) -> Self {
+        Self {
+            trace_id,
+            span_id,
+            sampling_priority: 1,
+            propagated: None,
+        }
+    }
+
+    /// Whether the trace was sampled i.e. is stored by the tracing backend
+    #[must_use]
+    pub fn with_sampled(self, sampled: bool) -> Self {
+        self.with_sampling_priority(i8::from(sampled))
+    }
+
+    /// The Datadog sampling priority of the trace.
+    ///
+    /// Traces with a priority above `0` are sampled, e.g. `2` if the user decided to keep it
+    /// and `-1` if the user decided to drop it.
+    #[must_use]
+    pub fn with_sampling_priority(self, sampling_priority: i8) -> Self {
+        Self {
+            sampling_priority,
+            ..self
+        }
+    }
+
+    /// The 128-bit trace id
+    #[must_use]
+    pub const fn trace_id(&self) -> u128 {
+        self.trace_id
+    }
+
+    /// The 64-bit span id
+    #[must_use]
+    pub const fn span_id(&self) -> u64 {
+        self.span_id
+    }
+
+    /// Whether the trace was sampled
+    #[must_use]
+    pub const fn is_sampled(&self) -> bool {
+        self.sampling_priority > 0
+    }
+
+    /// The Datadog sampling priority of the trace
+    #[must_use]
+    pub const fn sampling_priority(&self) -> i8 {
+        self.sampling_priority
+    }
+
+    /// Whether both ids are non-zero.
+    ///
+    /// Logs with invalid ids are not correlated,
+    /// because they would otherwise be linked to the non-existent trace `0`.
+    #[must_use]
+    pub const fn is_valid(&self) -> bool {
+        self.trace_id != 0 && self.span_id != 0
+    }
+
+    /// The ids of another span in the same trace, keeping the sampling priority and propagated state
+    pub(crate) fn with_span_id(self, span_id: u64) -> Self {
+        Self { span_id, ..self }
+    }
+
+    pub(crate) fn with_propagated(self, propagated: Option<PropagatedState>) -> Self {
+        Self {
+            propagated: propagated.map(Arc::new),
+            ..self
+        }
+    }
+
+    pub(crate) fn propagated(&self) -> Option<&PropagatedState> {
+        self.propagated.as_deref()
+    }
+
+    pub(crate) fn datadog_ids(&self) -> (DatadogTraceId, DatadogSpanId) {
+        (
+            DatadogTraceId::from(self.trace_id),
+            DatadogSpanId(self.span_id),
+        )
+    }
+}
+
+/// Provides the ids a log is correlated with
+///
+/// The layer asks its provider for the ids of every event and writes them as `dd.trace_id` and
+/// `dd.span_id`. This allows the ids to come from sources other than OpenTelemetry.
+///
+/// # Example
+/// ```
+/// use datadog_formatting_layer::{CorrelationIds, CorrelationProvider, DatadogFormattingLayer};
+/// use tracing::{Event, Subscriber};
+/// use tracing_subscriber::{layer::Context, registry::LookupSpan};
+///
+/// /// Correlates every log with the same trace
+/// #[derive(Debug, Clone)]
+/// struct Fixed(CorrelationIds);
+///
+/// impl CorrelationProvider for Fixed {
+///     fn correlation_ids<S>(
+///         &self,
+///         _ctx: &Context<'_, S>,
+///         _event: &Event<'_>,
+///     ) -> Option<CorrelationIds>
+///     where
+///         S: Subscriber + for<'a> LookupSpan<'a>,
+///     {
+///         Some(self.0.clone())
+///     }
+/// }
+///
+/// let layer =
+///     DatadogFormattingLayer::default().with_correlation(Fixed(CorrelationIds::new(1, 2)));
+/// ```
+pub trait CorrelationProvider {
+    /// Called once the layer is registered with a subscriber
+    fn on_register_dispatch(&self, _subscriber: &Dispatch) {}
+
+    /// Called when a new span is created e.g. to assign ids to it
+    fn on_new_span<S>(&self, _attrs: &Attributes<'_>, _id: &Id, _ctx: &Context<'_, S>)
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+    }
+
+    /// The ids the event is correlated with or `None` if it should not be correlated
+    fn correlation_ids<S>(&self, ctx: &Context<'_, S>, event: &Event<'_>) -> Option<CorrelationIds>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>;
+
+    /// The ids of the span e.g. for its summary log once it is closed.
+    ///
+    /// Defaults to `None`, so the summaries are not correlated.
+    fn span_correlation_ids<'a, S>(&self, _span_ref: &SpanRef<'a, S>) -> Option<CorrelationIds>
+    where
+        S: Subscriber + LookupSpan<'a>,
+    {
+        None
+    }
+}
+
+/// Never correlates logs with any trace
+#[non_exhaustive]
+#[derive(Debug, Clone, Default)]
+pub struct NoCorrelation;
+
+impl NoCorrelation {
+    /// Create a provider which never correlates logs with any trace
+    #[must_use]
+    pub const fn new() -> Self {
+        Self
+    }
+}
+
+impl CorrelationProvider for NoCorrelation {
+    fn correlation_ids<S>(
+        &self,
+        _ctx: &Context<'_, S>,
+        _event: &Event<'_>,
+    ) -> Option<CorrelationIds>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        None
+    }
+}
+
+/// Correlates logs using the ids of the tracing spans, without any distributed tracing
+///
+/// The span id is the id of the span the event belongs to and the trace id is the id of
+/// the root span of its scope. This groups the logs of one request without a tracer.
+///
+/// Tracing span ids are only unique among the spans which are currently open
+/// and are reused once a span is closed, so they must not be mixed with ids of a tracer.
+///
+/// # Example
+/// ```
+/// use datadog_formatting_layer::{DatadogFormattingLayer, TracingSpanCorrelation};
+///
+/// let layer = DatadogFormattingLayer::default().with_correlation(TracingSpanCorrelation::new());
+/// ```
+#[non_exhaustive]
+#[derive(Debug, Clone, Default)]
+pub struct TracingSpanCorrelation;
+
+impl TracingSpanCorrelation {
+    /// Create a provider which uses the ids of the tracing spans
+    #[must_use]
+    pub const fn new() -> Self {
+        Self
+    }
+}
+
+impl CorrelationProvider for TracingSpanCorrelation {
+    fn correlation_ids<S>(&self, ctx: &Context<'_, S>, event: &Event<'_>) -> Option<CorrelationIds>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        ctx.event_span(event)
+            .and_then(|span_ref| self.span_correlation_ids(&span_ref))
+    }
+
+    fn span_correlation_ids<'a, S>(&self, span_ref: &SpanRef<'a, S>) -> Option<CorrelationIds>
+    where
+        S: Subscriber + LookupSpan<'a>,
+    {
+        let root = span_ref.scope().from_root().next()?;
+
+        Some(CorrelationIds::new(
+            u128::from(root.id().into_u64()),
+            span_ref.id().into_u64(),
+        ))
+    }
+}
+
+#[derive(serde::Serialize)]
+#[cfg_attr(test, derive(Debug, Clone, Copy, serde::Deserialize, PartialEq, Eq))]
+pub struct DatadogTraceId(pub(crate) u64);
+
+#[allow(clippy::fallible_impl_from)]
+impl From<u128> for DatadogTraceId {
+    // TraceId are u128 -> 16 Bytes
+    // but datadog needs u64 -> 8 Bytes
+    // Therefore we just take the 8 least significant bytes
+    // This is not ideal and may lead to duplicate trace correlations,
+    // but we cannot do anything against that anyway.
+    fn from(value: u128) -> Self {
+        let bytes = value.to_be_bytes();
+        // this cannot fail
+        #[allow(clippy::unwrap_used)]
+        let least_significant_8_bytes = bytes.get(8..16).unwrap();
+
+        // this also cannot fail because we checked the range one line above
+        #[allow(clippy::unwrap_used)]
+        let bytes_as_sized_slice: [u8; 8] = least_significant_8_bytes.try_into().unwrap();
+
+        Self(u64::from_be_bytes(bytes_as_sized_slice))
+    }
+}
+
+#[derive(serde::Serialize)]
+#[cfg_attr(test, derive(Debug, Clone, Copy, serde::Deserialize, PartialEq, Eq))]
+pub struct DatadogSpanId(pub u64);
+
+/// The attribute names used for the OpenTelemetry-style hex trace and span ids
+///
+/// # Example
+/// ```
+/// use datadog_formatting_layer::{DatadogFormattingLayer, HexIdKeys};
+///
+/// // emits "otel.trace_id" and "otel.span_id"
+/// let layer = DatadogFormattingLayer::default().with_hex_ids(HexIdKeys::default());
+///
+/// // emits "trace_id" and "span_id"
+/// let layer = DatadogFormattingLayer::default().with_hex_ids(HexIdKeys::plain());
+///
+/// // emits custom attribute names
+/// let layer =
+///     DatadogFormattingLayer::default().with_hex_ids(HexIdKeys::new("otel.trace", "otel.span"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HexIdKeys {
+    trace_id: String,
+    span_id: String,
+}
+
+impl HexIdKeys {
+    /// Use custom attribute names for the trace and span id
+    pub fn new(trace_id: impl Into<String>, span_id: impl Into<String>) -> Self {
+        Self {
+            trace_id: trace_id.into(),
+            span_id: span_id.into(),
+        }
+    }
+
+    /// Use `trace_id` and `span_id` as attribute names
+    #[must_use]
+    pub fn plain() -> Self {
+        Self::new("trace_id", "span_id")
+    }
+
+    /// The trace and span id formatted as lowercase hex strings
+    /// (32 and 16 characters) like OpenTelemetry does
+    pub(crate) fn to_attributes(&self, ids: &CorrelationIds) -> [(String, Value); 2] {
+        [
+            (
+                self.trace_id.clone(),
+                format!("{:032x}", ids.trace_id).into(),
+            ),
+            (self.span_id.clone(), format!("{:016x}", ids.span_id).into()),
+        ]
+    }
+}
+
+impl Default for HexIdKeys {
+    /// Use `otel.trace_id` and `otel.span_id` as attribute names
+    fn default() -> Self {
+        Self::new("otel.trace_id", "otel.span_id")
+    }
+}
@@ -1,53 +1,105 @@
-use opentelemetry::trace::{SpanId, TraceId};
-use tracing::Subscriber;
-use tracing_opentelemetry::OtelData;
-use tracing_subscriber::{layer::Context, registry::LookupSpan};
-
-#[derive(serde::Serialize)]
-#[cfg_attr(test, derive(Debug, Clone, Copy, serde::Deserialize, PartialEq, Eq))]
-pub struct DatadogTraceId(pub(crate) u64);
-
-#[allow(clippy::fallible_impl_from)]
-impl From<TraceId> for DatadogTraceId {
-    // TraceId are u128 -> 16 Bytes
-    // but datadog needs u64 -> 8 Bytes
-    // Therefore we just take the 8 most significant bytes
-    // This is not ideal and may lead to duplicate trace correlations,
-    // but we cannot do anything against that anyway.
-    fn from(value: TraceId) -> Self {
-        let bytes = value.to_bytes();
-        // this cannot fail
-        #[allow(clippy::unwrap_used)]
-        let most_significant_8_bytes = bytes.get(8..16).unwrap();
-
-        // this also cannot fail because we checked the range one line above
-        #[allow(clippy::unwrap_used)]
-        let bytes_as_sized_slice: [u8; 8] = most_significant_8_bytes.try_into().unwrap();
-
-        Self(u64::from_be_bytes(bytes_as_sized_slice))
+#[cfg(feature = "otel-0_30")]
+use crate::otel::Otel030;
+#[cfg(feature = "otel-0_31")]
+use crate::otel::Otel031;
+use crate::{
+    correlation::{CorrelationIds, CorrelationProvider},
+    otel::{read_otel_context, OtelVersion},
+};
+use std::sync::OnceLock;
+use tracing::{dispatcher::WeakDispatch, Dispatch, Event, Subscriber};
+use tracing_subscriber::{
+    layer::Context,
+    registry::{LookupSpan, SpanRef},
+};
+
+/// Correlates logs with the OpenTelemetry span they belong to
+///
+/// This is the default correlation provider when the `opentelemetry` feature is enabled.
+/// The ids are taken from the span of the event if it is tracked by `tracing-opentelemetry`,
+/// or from the active OpenTelemetry context otherwise.
+/// If several `otel-*` features are enabled, the newest version with valid ids is used.
+#[derive(Debug, Clone, Default)]
+pub struct OtelCorrelation {
+    dispatch: OnceLock<WeakDispatch>,
+}
+
+impl OtelCorrelation {
+    /// Create a provider which reads the ids from the OpenTelemetry context
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            dispatch: OnceLock::new(),
+        }
     }
 }
 
-#[derive(serde::Serialize)]
-#[cfg_attr(test, derive(Debug, Clone, Copy, serde::Deserialize, PartialEq, Eq))]
-pub struct DatadogSpanId(pub u64);
+impl CorrelationProvider for OtelCorrelation {
+    fn on_register_dispatch(&self, subscriber: &Dispatch) {
+        // the otel context can only be read with access to the subscriber
+        let _already_registered = self.dispatch.set(subscriber.downgrade());
+    }
+
+    fn correlation_ids<S>(&self, ctx: &Context<'_, S>, event: &Event<'_>) -> Option<CorrelationIds>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        let dispatch = self.dispatch.get().and_then(WeakDispatch::upgrade);
+
+        #[cfg(feature = "otel-0_31")]
+        if let Some(ids) = event_ids::<Otel031, S>(ctx, event, dispatch.as_ref()) {
+            return Some(ids);
+        }
+        #[cfg(feature = "otel-0_30")]
+        if let Some(ids) = event_ids::<Otel030, S>(ctx, event, dispatch.as_ref()) {
+            return Some(ids);
+        }
+        None
+    }
+
+    fn span_correlation_ids<'a, S>(&self, span_ref: &SpanRef<'a, S>) -> Option<CorrelationIds>
+    where
+        S: Subscriber + LookupSpan<'a>,
+    {
+        let dispatch = self.dispatch.get().and_then(WeakDispatch::upgrade);
 
-impl From<SpanId> for DatadogSpanId {
-    fn from(value: SpanId) -> Self {
-        Self(u64::from_be_bytes(value.to_bytes()))
+        #[cfg(feature = "otel-0_31")]
+        if let Some(ids) = span_ids::<Otel031, S>(span_ref, dispatch.as_ref()) {
+            return Some(ids);
+        }
+        #[cfg(feature = "otel-0_30")]
+        if let Some(ids) = span_ids::<Otel030, S>(span_ref, dispatch.as_ref()) {
+            return Some(ids);
+        }
+        None
     }
 }
 
-pub fn read_from_context<S>(ctx: &Context<'_, S>) -> Option<(DatadogTraceId, DatadogSpanId)>
+/// The valid ids of the OpenTelemetry context of an event in one otel version
+fn event_ids<V, S>(
+    ctx: &Context<'_, S>,
+    event: &Event<'_>,
+    dispatch: Option<&Dispatch>,
+) -> Option<CorrelationIds>
 where
+    V: OtelVersion,
     S: Subscriber + for<'a> LookupSpan<'a>,
 {
-    ctx.lookup_current().and_then(|span_ref| {
-        span_ref.extensions().get::<OtelData>().map(|otel| {
-            let trace_id = otel.trace_id().unwrap_or(TraceId::INVALID);
-            let span_id = otel.span_id().unwrap_or(SpanId::INVALID);
-
-            (trace_id.into(), span_id.into())
-        })
-    })
+    read_otel_context::<V, S>(ctx, event, dispatch)
+        .map(|otel_context| V::correlation_ids(&otel_context))
+        .filter(CorrelationIds::is_valid)
+}
+
+/// The valid ids of the otel span of a tracing span in one otel version
+fn span_ids<'a, V, S>(
+    span_ref: &SpanRef<'a, S>,
+    dispatch: Option<&Dispatch>,
+) -> Option<CorrelationIds>
+where
+    V: OtelVersion,
+    S: Subscriber + LookupSpan<'a>,
+{
+    V::span_context(span_ref, dispatch)
+        .map(|otel_context| V::correlation_ids(&otel_context))
+        .filter(CorrelationIds::is_valid)
 }
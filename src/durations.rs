@@ -0,0 +1,96 @@
+use std::{cell::Cell, fmt, time::Duration};
+
+thread_local! {
+    /// The duration of the last [`Nanos`] formatted on this thread
+    static FORMATTED: Cell<Option<Duration>> = const { Cell::new(None) };
+}
+
+/// Marks a duration to be written as integer nanoseconds, Datadog's unit for durations
+///
+/// Its `Debug` and `Display` implementations write the readable `Debug` output of the duration
+/// e.g. `12.3ms`, also in other layers. With
+/// [`DatadogFormattingLayer::with_normalized_durations`](crate::DatadogFormattingLayer::with_normalized_durations)
+/// the layer writes the field as nanoseconds instead, while the message keeps the readable value.
+///
+/// # Example
+/// ```
+/// use datadog_formatting_layer::Nanos;
+/// use std::time::Duration;
+/// use tracing::info;
+///
+/// let elapsed = Nanos(Duration::from_micros(12_300));
+///
+/// // writes "fields.elapsed": 12300000 if durations are normalized
+/// info!(elapsed = ?elapsed, "Queried");
+/// assert_eq!(format!("{elapsed}"), "12.3ms");
+/// ```
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Nanos(pub Duration);
+
+impl Nanos {
+    /// The wrapped duration
+    #[must_use]
+    pub const fn into_inner(self) -> Duration {
+        self.0
+    }
+}
+
+impl From<Duration> for Nanos {
+    fn from(duration: Duration) -> Self {
+        Self(duration)
+    }
+}
+
+impl fmt::Debug for Nanos {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        FORMATTED.set(Some(self.0));
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl fmt::Display for Nanos {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// Formats the value of a field and returns its nanoseconds if it is a [`Nanos`]
+///
+/// Values which only contain a [`Nanos`] e.g. a struct with a duration field are not converted.
+pub fn format(value: &dyn fmt::Debug) -> (String, Option<u64>) {
+    FORMATTED.set(None);
+    let formatted = format!("{value:?}");
+
+    let nanos = FORMATTED
+        .take()
+        .filter(|duration| formatted == format!("{duration:?}"))
+        .map(|duration| u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX));
+
+    (formatted, nanos)
+}
+
+#[cfg(test)]
+mod formatting {
+    use super::{format, Nanos};
+    use smoothy::prelude::*;
+    use std::time::Duration;
+
+    #[test]
+    fn wrapped_durations_have_their_nanoseconds() {
+        for nanos in [0, 1, 12_300_000, 1_500_000_001, 3_600_000_000_000] {
+            let duration = Duration::from_nanos(nanos);
+
+            assert_that(format(&Nanos(duration))).is((format!("{duration:?}"), Some(nanos)));
+        }
+    }
+
+    #[test]
+    fn other_values_have_none() {
+        let display = format_args!("{}", "5s");
+
+        assert_that(format(&Duration::from_secs(5))).is(("5s".to_string(), None));
+        assert_that(format(&display)).is(("5s".to_string(), None));
+        assert_that(format(&"5s")).is(("\"5s\"".to_string(), None));
+        assert_that(format(&Some(Nanos(Duration::from_secs(5)))).1).is(None);
+    }
+}
@@ -0,0 +1,101 @@
+use crate::{fields::FieldPair, otel::OtelVersion};
+use tracing_subscriber::registry::{LookupSpan, SpanRef};
+
+/// Marks the OpenTelemetry span an ERROR event belongs to as errored
+///
+/// Sets the status of the span to error and copies `error.kind` and `error.message` onto the span,
+/// so the trace shows up as failed in APM.
+/// The error message is taken from the `error.message` or `error` field of the event
+/// and falls back to the log message. `error.kind` is only set if the event has such a field.
+///
+/// # Example
+/// ```
+/// use datadog_formatting_layer::{DatadogFormattingLayer, OtelErrorStatus};
+///
+/// let layer = DatadogFormattingLayer::default()
+///     .with_otel_error_status(OtelErrorStatus::default().with_span_events());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OtelErrorStatus {
+    span_events: bool,
+}
+
+impl OtelErrorStatus {
+    /// Also record the ERROR log as an event on the OpenTelemetry span.
+    ///
+    /// Only needed if `tracing-opentelemetry` does not see the event itself e.g. because of a filter.
+    #[must_use]
+    pub const fn with_span_events(self) -> Self {
+        Self { span_events: true }
+    }
+
+    /// Records the error on the otel span of the tracing span or, if there is none, of the context
+    pub(crate) fn record<'a, V: OtelVersion, S: LookupSpan<'a>>(
+        &self,
+        span_ref: Option<&SpanRef<'a, S>>,
+        otel_context: &V::Context,
+        message: &str,
+        fields: &[FieldPair],
+    ) {
+        V::record_error(span_ref, otel_context, self.span_error(message, fields));
+    }
+
+    fn span_error(&self, message: &str, fields: &[FieldPair]) -> SpanError {
+        let field = |name: &str| {
+            fields
+                .iter()
+                .find(|field| field.name == name)
+                .map(|field| field.value.trim_matches('\"').to_string())
+        };
+
+        let error_message = field("error.message")
+            .or_else(|| field("error"))
+            .unwrap_or_else(|| message.to_string());
+
+        let mut attributes = vec![("error.message", error_message.clone())];
+        if let Some(error_kind) = field("error.kind") {
+            attributes.push(("error.kind", error_kind));
+        }
+
+        let event = self.span_events.then(|| {
+            SpanEvent {
+                name: message.to_string(),
+                attributes: fields
+                    .iter()
+                    .filter(|field| field.name != "message")
+                    .map(|field| {
+                        (
+                            field.name.clone(),
+                            field.value.trim_matches('\"').to_string(),
+                        )
+                    })
+                    .chain([("level".to_string(), "ERROR".to_string())])
+                    .collect(),
+            }
+        });
+
+        SpanError {
+            message: error_message,
+            attributes,
+            event,
+        }
+    }
+}
+
+/// What is recorded on the otel span of an ERROR event
+pub struct SpanError {
+    /// The description of the error status
+    pub message: String,
+    /// `error.message` and, if the event has it, `error.kind`
+    pub attributes: Vec<(&'static str, String)>,
+    /// The event itself if span events are enabled
+    pub event: Option<SpanEvent>,
+}
+
+/// An ERROR event recorded on the otel span
+pub struct SpanEvent {
+    /// The message of the event
+    pub name: String,
+    /// The fields of the event and its level
+    pub attributes: Vec<(String, String)>,
+}
@@ -0,0 +1,121 @@
+use crate::{
+    fields::{FieldPair, ScopedFields},
+    formatting::insert_or_append,
+};
+use serde_json::{Map, Value};
+
+/// From which spans a log inherits fields
+///
+/// # Example
+/// ```
+/// use datadog_formatting_layer::{DatadogFormattingLayer, SpanFieldInheritance};
+///
+/// // only inherit the fields of the span the event belongs to and its parent
+/// let layer = DatadogFormattingLayer::default()
+///     .with_span_field_inheritance(SpanFieldInheritance::Nearest(2));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SpanFieldInheritance {
+    /// Inherit the fields of all spans in the scope of the log
+    #[default]
+    All,
+    /// Only inherit the fields of the nearest N spans, starting at the span of the log
+    Nearest(usize),
+    /// Do not inherit any span fields
+    Disabled,
+}
+
+impl SpanFieldInheritance {
+    /// The maximum number of spans to inherit fields from
+    pub(crate) const fn limit(self) -> usize {
+        match self {
+            Self::All => usize::MAX,
+            Self::Nearest(spans) => spans,
+            Self::Disabled => 0,
+        }
+    }
+}
+
+/// What happens if several spans of a log or the event itself have fields with the same name
+///
+/// # Example
+/// ```
+/// use datadog_formatting_layer::{DatadogFormattingLayer, FieldCollisions};
+///
+/// // writes e.g. "span_fields.request.user": "Jack" instead of "fields.user": "Jack"
+/// let layer =
+///     DatadogFormattingLayer::default().with_field_collisions(FieldCollisions::NamespaceBySpan);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FieldCollisions {
+    /// The field of the event or the innermost span is written
+    #[default]
+    InnermostWins,
+    /// The field of the outermost span is written
+    OutermostWins,
+    /// All values are written as an array, starting at the outermost span
+    KeepAll,
+    /// Span fields are written as `span_fields.<span name>.<field>` and are not added to the message,
+    /// only the event fields are written as `fields.<field>`.
+    ///
+    /// The values of nested spans with the same name are written as an array,
+    /// starting at the outermost span.
+    NamespaceBySpan,
+}
+
+impl FieldCollisions {
+    /// Resolves the collisions between the span and event fields.
+    ///
+    /// Returns the fields of the log and the attributes for the namespaced span fields.
+    pub(crate) fn resolve(
+        self,
+        spans: Vec<ScopedFields>,
+        event_fields: Vec<FieldPair>,
+    ) -> (Vec<FieldPair>, Vec<(String, Value)>) {
+        if self == Self::NamespaceBySpan {
+            let mut namespaced = Map::new();
+            for mut scoped in spans {
+                scoped.fields.sort();
+                for field in scoped.fields {
+                    insert_or_append(
+                        &mut namespaced,
+                        format!("span_fields.{}.{}", scoped.span_name, field.name),
+                        field.value.trim_matches('\"').into(),
+                    );
+                }
+            }
+
+            return (event_fields, namespaced.into_iter().collect());
+        }
+
+        let all_fields = spans
+            .into_iter()
+            .flat_map(|scoped| scoped.fields)
+            .chain(event_fields);
+
+        let fields = match self {
+            Self::InnermostWins => {
+                let mut fields = dedup_by_name(all_fields.rev());
+                fields.reverse();
+                fields
+            }
+            Self::OutermostWins => dedup_by_name(all_fields),
+            Self::KeepAll | Self::NamespaceBySpan => all_fields.collect(),
+        };
+
+        (fields, Vec::new())
+    }
+}
+
+/// Keeps the first field of every name
+fn dedup_by_name(fields: impl Iterator<Item = FieldPair>) -> Vec<FieldPair> {
+    let mut unique: Vec<FieldPair> = Vec::new();
+
+    for field in fields {
+        if !unique.iter().any(|existing| existing.name == field.name) {
+            unique.push(field);
+        }
+    }
+
+    unique
+}
@@ -0,0 +1,174 @@
+use crate::{fields::FieldPair, redaction::glob_matches};
+use serde_json::Value;
+
+/// The keys the layer writes itself, which moved fields must not overwrite
+const RESERVED_KEYS: [&str; 8] = [
+    "timestamp",
+    "level",
+    "message",
+    "target",
+    "dd.trace_id",
+    "dd.span_id",
+    "span",
+    "spans",
+];
+
+/// Drops, renames and moves the fields of spans and events before they are written
+///
+/// Fields are matched by name or by a glob in which `*` matches anything, targets by
+/// their module path including submodules (`hyper` matches `hyper::client`).
+/// Rules are applied to the original names, the `message` field is never dropped or renamed.
+/// Unlike [`FieldRedaction`](crate::FieldRedaction), names and globs are matched case-sensitively,
+/// as field names are written as they are.
+///
+/// Renamed fields are still written as `fields.<new name>` and added to the message,
+/// moved fields are written as attribute at their new path instead e.g. `usr.id`.
+///
+/// # Example
+/// ```
+/// use datadog_formatting_layer::{DatadogFormattingLayer, FieldRules};
+///
+/// let layer = DatadogFormattingLayer::default().with_field_rules(
+///     FieldRules::default()
+///         .with_denied_target("hyper")
+///         .with_denied_field("*_internal")
+///         .with_renamed_field("uid", "user_id")
+///         .with_moved_field("user_id", "usr.id"),
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FieldRules {
+    denied_fields: Vec<String>,
+    denied_targets: Vec<String>,
+    allowed_fields: Vec<String>,
+    renamed_fields: Vec<(String, String)>,
+    moved_fields: Vec<(String, String)>,
+}
+
+impl FieldRules {
+    /// Drop fields with this name or matching this glob
+    #[must_use]
+    pub fn with_denied_field(mut self, pattern: impl Into<String>) -> Self {
+        self.denied_fields.push(pattern.into());
+        self
+    }
+
+    /// Drop all fields of spans and events with this target or a target in its submodules
+    #[must_use]
+    pub fn with_denied_target(mut self, target: impl Into<String>) -> Self {
+        self.denied_targets.push(target.into());
+        self
+    }
+
+    /// Only keep fields with this name or matching this glob, together with all other allowed ones
+    #[must_use]
+    pub fn with_allowed_field(mut self, pattern: impl Into<String>) -> Self {
+        self.allowed_fields.push(pattern.into());
+        self
+    }
+
+    /// Write the field as `fields.<to>` instead
+    #[must_use]
+    pub fn with_renamed_field(mut self, from: impl Into<String>, to: impl Into<String>) -> Self {
+        self.renamed_fields.push((from.into(), to.into()));
+        self
+    }
+
+    /// Write the field as attribute at this path instead of `fields.<field>` and the message
+    ///
+    /// Paths the layer writes itself (`timestamp`, `level`, `message`, `target`, `dd.trace_id`,
+    /// `dd.span_id`, `span`, `spans`, `fields.*` and `span_fields.*`) can not be overwritten,
+    /// the field is renamed to them instead.
+    /// The same happens if a log already has an attribute at the path, e.g. the hex ids
+    /// or the `duration` of a span summary.
+    #[must_use]
+    pub fn with_moved_field(mut self, from: impl Into<String>, to: impl Into<String>) -> Self {
+        let path = to.into();
+        if is_reserved(&path) {
+            self.renamed_fields.push((from.into(), path));
+        } else {
+            self.moved_fields.push((from.into(), path));
+        }
+        self
+    }
+
+    /// Drops and renames the fields of a span or event
+    ///
+    /// Moved fields get their new path as name, so [`FieldRules::split_moved`] can find them.
+    pub(crate) fn apply(&self, target: &str, fields: Vec<FieldPair>) -> Vec<FieldPair> {
+        let target_denied = self
+            .denied_targets
+            .iter()
+            .any(|denied| target_matches(denied, target));
+
+        fields
+            .into_iter()
+            .filter(|field| field.name == "message" || (!target_denied && self.keeps(&field.name)))
+            .map(|field| {
+                match self.new_name(&field.name) {
+                    Some(name) if field.name != "message" => {
+                        FieldPair {
+                            name: name.to_string(),
+                            ..field
+                        }
+                    }
+                    _ => field,
+                }
+            })
+            .collect()
+    }
+
+    /// Splits the moved fields from the other ones, returning them as attributes
+    ///
+    /// Fields moved to a path which is already `written` are kept as renamed field.
+    pub(crate) fn split_moved(
+        &self,
+        fields: Vec<FieldPair>,
+        written: impl Fn(&str) -> bool,
+    ) -> (Vec<FieldPair>, Vec<(String, Value)>) {
+        let (mut moved, kept): (Vec<FieldPair>, Vec<FieldPair>) =
+            fields.into_iter().partition(|field| {
+                self.moved_fields.iter().any(|(_, to)| *to == field.name) && !written(&field.name)
+            });
+        moved.sort();
+
+        let attributes = moved
+            .into_iter()
+            .map(|field| (field.name, field.value.trim_matches('\"').into()))
+            .collect();
+
+        (kept, attributes)
+    }
+
+    fn keeps(&self, name: &str) -> bool {
+        let denied = self
+            .denied_fields
+            .iter()
+            .any(|pattern| glob_matches(pattern, name));
+        let allowed = self.allowed_fields.is_empty()
+            || self
+                .allowed_fields
+                .iter()
+                .any(|pattern| glob_matches(pattern, name));
+
+        !denied && allowed
+    }
+
+    fn new_name(&self, name: &str) -> Option<&str> {
+        self.renamed_fields
+            .iter()
+            .chain(&self.moved_fields)
+            .find(|(from, _)| from == name)
+            .map(|(_, to)| to.as_str())
+    }
+}
+
+fn is_reserved(path: &str) -> bool {
+    RESERVED_KEYS.contains(&path) || path.starts_with("fields.") || path.starts_with("span_fields.")
+}
+
+fn target_matches(denied: &str, target: &str) -> bool {
+    target
+        .strip_prefix(denied)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+}
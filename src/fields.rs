@@ -1,8 +1,13 @@
+use crate::{durations, field_policy::SpanFieldInheritance};
 use std::{cmp::Ordering, collections::HashMap};
-use tracing::{field::Visit, span::Attributes, Event, Subscriber};
+use tracing::{
+    field::Visit,
+    span::{Attributes, Record},
+    Event, Subscriber,
+};
 use tracing_subscriber::{
     layer::Context,
-    registry::{LookupSpan, Scope},
+    registry::{LookupSpan, SpanRef},
 };
 
 #[derive(Debug, Clone)]
@@ -10,10 +15,24 @@ pub struct FieldStore {
     pub fields: Vec<FieldPair>,
 }
 
+impl FieldStore {
+    /// Overwrite existing fields with the recorded values or add them if they are new
+    pub fn record(&mut self, recorded: Vec<FieldPair>) {
+        for pair in recorded {
+            match self.fields.iter_mut().find(|field| field.name == pair.name) {
+                Some(field) => *field = pair,
+                None => self.fields.push(pair),
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct FieldPair {
     pub name: String,
     pub value: String,
+    /// The nanoseconds of the value if it was recorded as [`Nanos`](crate::Nanos)
+    pub nanos: Option<u64>,
 }
 
 impl PartialOrd for FieldPair {
@@ -32,32 +51,66 @@ pub fn from_attributes(attrs: &Attributes<'_>) -> Vec<FieldPair> {
     let mut visitor = Visitor::default();
     attrs.values().record(&mut visitor);
 
-    visitor
-        .fields
-        .into_iter()
-        .map(|(key, value)| FieldPair { name: key, value })
-        .collect()
+    visitor.into_fields()
+}
+
+pub fn from_record(values: &Record<'_>) -> Vec<FieldPair> {
+    let mut visitor = Visitor::default();
+    values.record(&mut visitor);
+
+    visitor.into_fields()
 }
 
 pub fn from_event(event: &Event<'_>) -> Vec<FieldPair> {
     let mut visitor = Visitor::default();
     event.record(&mut visitor);
 
-    visitor
-        .fields
-        .into_iter()
-        .map(|(key, value)| FieldPair { name: key, value })
-        .collect()
+    visitor.into_fields()
+}
+
+/// The fields of a span in the scope of a log
+#[derive(Debug, Clone)]
+pub struct ScopedFields {
+    pub span_name: &'static str,
+    pub fields: Vec<FieldPair>,
 }
 
 pub fn from_spans<S: Subscriber + for<'a> LookupSpan<'a>>(
     ctx: &Context<'_, S>,
     event: &Event<'_>,
-) -> Vec<FieldPair> {
-    ctx.event_scope(event)
-        .into_iter()
-        .flat_map(Scope::from_root)
-        .flat_map(|span| {
+    inheritance: SpanFieldInheritance,
+) -> Vec<ScopedFields> {
+    ctx.event_span(event)
+        .map(|span_ref| from_scope(&span_ref, inheritance))
+        .unwrap_or_default()
+}
+
+/// The fields of the span and its parents, starting at the outermost inherited span
+pub fn from_scope<'a, S: LookupSpan<'a>>(
+    span_ref: &SpanRef<'a, S>,
+    inheritance: SpanFieldInheritance,
+) -> Vec<ScopedFields> {
+    from_nearest_spans(span_ref, inheritance.limit())
+}
+
+/// The fields of a closed span and its inherited parents for its summary
+///
+/// The span's own fields are always included, the inheritance only limits the parents.
+pub fn from_closed_span<'a, S: LookupSpan<'a>>(
+    span_ref: &SpanRef<'a, S>,
+    inheritance: SpanFieldInheritance,
+) -> Vec<ScopedFields> {
+    from_nearest_spans(span_ref, inheritance.limit().max(1))
+}
+
+fn from_nearest_spans<'a, S: LookupSpan<'a>>(
+    span_ref: &SpanRef<'a, S>,
+    spans: usize,
+) -> Vec<ScopedFields> {
+    let mut scope: Vec<ScopedFields> = span_ref
+        .scope()
+        .take(spans)
+        .map(|span| {
             #[allow(clippy::expect_used)]
             let fields_from_span = span
                 .extensions()
@@ -65,19 +118,34 @@ pub fn from_spans<S: Subscriber + for<'a> LookupSpan<'a>>(
                 .expect("No Fields found in span extensions")
                 .clone();
 
-            fields_from_span.fields
+            ScopedFields {
+                span_name: span.name(),
+                fields: fields_from_span.fields,
+            }
         })
-        .collect()
+        .collect();
+
+    scope.reverse();
+    scope
 }
 
 #[derive(Default)]
 struct Visitor {
-    fields: HashMap<String, String>,
+    fields: HashMap<String, (String, Option<u64>)>,
+}
+
+impl Visitor {
+    fn into_fields(self) -> Vec<FieldPair> {
+        self.fields
+            .into_iter()
+            .map(|(name, (value, nanos))| FieldPair { name, value, nanos })
+            .collect()
+    }
 }
 
 impl Visit for Visitor {
     fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
         self.fields
-            .insert(field.name().to_string(), format!("{value:?}"));
+            .insert(field.name().to_string(), durations::format(value));
     }
 }
@@ -1,24 +1,37 @@
 use crate::{
-    datadog_ids::{DatadogSpanId, DatadogTraceId},
+    correlation::{DatadogSpanId, DatadogTraceId},
     fields::FieldPair,
+    process_metadata::ProcessMetadata,
+    standard_attributes,
 };
 use chrono::{DateTime, Utc};
 use serde_json::{Map, Value};
-use std::fmt::Write;
+use std::{borrow::Cow, fmt::Write};
 use tracing::Level;
 
 /// All the data required to create a Datadog-compatible log
 #[cfg_attr(test, derive(Debug, Clone))]
-pub struct DatadogLog {
+pub struct DatadogLog<'a> {
     pub timestamp: DateTime<Utc>,
     pub level: Level,
     pub message: String,
     pub fields: Vec<FieldPair>,
     pub target: String,
     pub datadog_ids: Option<(DatadogTraceId, DatadogSpanId)>,
+    /// Additional attributes which are written after the datadog ids
+    pub attributes: Vec<(String, Value)>,
+    /// Attributes which are only written if the log does not contain them yet,
+    /// ordered by precedence
+    pub global_attributes: Vec<(String, Value)>,
+    /// Written unless other attributes have the same key, taking precedence over the global ones
+    pub process_metadata: Option<&'a ProcessMetadata>,
+    /// Write well-known fields as Datadog standard attributes instead of `fields.<field>`
+    pub standard_attributes: bool,
+    /// Write fields recorded as [`Nanos`](crate::Nanos) as nanoseconds
+    pub normalize_durations: bool,
 }
 
-impl DatadogLog {
+impl DatadogLog<'_> {
     pub fn format(mut self) -> String {
         let mut log = Map::new();
 
@@ -28,16 +41,34 @@ impl DatadogLog {
         self.fields.sort();
 
         let mut message = self.message;
+        let mut standard = Vec::new();
 
         for field in &self.fields {
             // message is just a regular field
             if field.name != "message" {
                 let value = field.value.trim_matches('\"');
+                let nanos = field.nanos.filter(|_| self.normalize_durations);
 
+                if self.standard_attributes {
+                    let normalized = nanos.map_or(Cow::Borrowed(value), |duration| {
+                        Cow::Owned(duration.to_string())
+                    });
+
+                    if let Some(attribute) = standard_attributes::map(&field.name, &normalized) {
+                        standard.push(attribute);
+                        continue;
+                    }
+                }
+
+                // the message keeps the readable value e.g. of durations
                 // should never fail
                 #[allow(clippy::expect_used)]
                 write!(message, " {}={}", field.name, value).expect("Failed to write to message");
-                log.insert(format!("fields.{}", &field.name), value.into());
+                insert_or_append(
+                    &mut log,
+                    format!("fields.{}", &field.name),
+                    nanos.map_or_else(|| value.into(), Value::from),
+                );
             }
         }
 
@@ -50,8 +81,75 @@ impl DatadogLog {
             log.insert("dd.span_id".to_string(), span_id.0.into());
         }
 
-        serde_json::to_string(&Value::from(log))
-            .unwrap_or_else(|err| format!("Failed to serialize a log to json: {err}"))
+        // like regular fields, values mapped to the same attribute are all kept
+        for (key, value) in standard {
+            insert_or_append(&mut log, key.to_string(), value);
+        }
+
+        log.extend(self.attributes);
+
+        for (key, value) in self.global_attributes {
+            if !self
+                .process_metadata
+                .is_some_and(|metadata| metadata.contains(&key))
+            {
+                log.entry(key).or_insert(value);
+            }
+        }
+
+        let mut json = match serde_json::to_string(&log) {
+            Ok(json) => json,
+            Err(err) => return format!("Failed to serialize a log to json: {err}"),
+        };
+
+        // the metadata is already serialized, so it is appended to the object as it is
+        if let Some(metadata) = self.process_metadata {
+            json.pop();
+            metadata.for_each(|key, value| {
+                if !log.contains_key(key) {
+                    json.push_str(",\"");
+                    json.push_str(key);
+                    json.push_str("\":");
+                    json.push_str(value);
+                }
+            });
+            json.push('}');
+        }
+
+        json
+    }
+}
+
+/// Whether the layer writes this key itself or Datadog reserves it,
+/// so attributes read from OpenTelemetry must not overwrite it
+#[cfg(any(feature = "otel-0_30", feature = "otel-0_31"))]
+pub fn is_core_key(key: &str) -> bool {
+    [
+        "timestamp",
+        "level",
+        "message",
+        "target",
+        "host",
+        "service",
+        "status",
+        "env",
+        "version",
+        "ddsource",
+        "ddtags",
+    ]
+    .contains(&key)
+        || key.starts_with("dd.")
+        || key.starts_with("fields.")
+}
+
+/// Inserts the value or, if the key is already present, turns the values into an array
+pub fn insert_or_append(log: &mut Map<String, Value>, key: String, value: Value) {
+    match log.get_mut(&key) {
+        Some(Value::Array(values)) => values.push(value),
+        Some(existing) => *existing = Value::Array(vec![existing.take(), value]),
+        None => {
+            log.insert(key, value);
+        }
     }
 }
 
@@ -71,6 +169,11 @@ mod format {
             fields: vec![],
             target: "target".to_string(),
             datadog_ids: None,
+            attributes: vec![],
+            standard_attributes: false,
+            normalize_durations: false,
+            global_attributes: vec![],
+            process_metadata: None,
         };
 
         assert_that(trace.clone().format()).contains("\"level\":\"TRACE\"");
@@ -109,6 +212,11 @@ mod format {
             fields: vec![],
             target: "target".to_string(),
             datadog_ids: None,
+            attributes: vec![],
+            standard_attributes: false,
+            normalize_durations: false,
+            global_attributes: vec![],
+            process_metadata: None,
         };
 
         assert_that(sut.format()).is(json!({"timestamp": "2022-01-01T00:00:00+00:00", "level": "INFO", "message": "Hello World!", "target": "target"}).to_string());
@@ -123,16 +231,220 @@ mod format {
             fields: vec![],
             target: "target".to_string(),
             datadog_ids: Some((DatadogTraceId(1), DatadogSpanId(2))),
+            attributes: vec![],
+            standard_attributes: false,
+            normalize_durations: false,
+            global_attributes: vec![],
+            process_metadata: None,
         };
 
         assert_that(sut.format()).is(json!({"timestamp": "2022-01-01T00:00:00+00:00", "level": "INFO", "message": "Hello World!", "target": "target", "dd.trace_id": 1, "dd.span_id": 2}).to_string());
     }
 
+    #[test]
+    fn attributes_are_written_after_datadog_ids() {
+        let sut = DatadogLog {
+            timestamp: timestamp!("2022-01-01T00:00:00Z"),
+            level: Level::INFO,
+            message: "Hello World!".to_string(),
+            fields: vec![],
+            target: "target".to_string(),
+            datadog_ids: Some((DatadogTraceId(1), DatadogSpanId(2))),
+            attributes: vec![
+                (
+                    "otel.trace_id".to_string(),
+                    "00000000000000000000000000000001".into(),
+                ),
+                ("otel.span_id".to_string(), "0000000000000002".into()),
+            ],
+            standard_attributes: false,
+            normalize_durations: false,
+            global_attributes: vec![],
+            process_metadata: None,
+        };
+
+        assert_that(sut.format()).is(json!({"timestamp": "2022-01-01T00:00:00+00:00", "level": "INFO", "message": "Hello World!", "target": "target", "dd.trace_id": 1, "dd.span_id": 2, "otel.trace_id": "00000000000000000000000000000001", "otel.span_id": "0000000000000002"}).to_string());
+    }
+
+    #[test]
+    fn global_attributes_do_not_overwrite_the_log() {
+        let sut = DatadogLog {
+            timestamp: timestamp!("2022-01-01T00:00:00Z"),
+            level: Level::INFO,
+            message: "Hello World!".to_string(),
+            fields: vec![],
+            target: "target".to_string(),
+            datadog_ids: None,
+            attributes: vec![("service".to_string(), "from-attributes".into())],
+            standard_attributes: false,
+            normalize_durations: false,
+            global_attributes: vec![
+                ("tenant.id".to_string(), "dynamic".into()),
+                ("message".to_string(), "global".into()),
+                ("service".to_string(), "global".into()),
+                ("tenant.id".to_string(), "static".into()),
+                ("ddtags".to_string(), "team:payments".into()),
+            ],
+            process_metadata: None,
+        };
+
+        assert_that(sut.format()).is(json!({"timestamp": "2022-01-01T00:00:00+00:00", "level": "INFO", "message": "Hello World!", "target": "target", "service": "from-attributes", "tenant.id": "dynamic", "ddtags": "team:payments"}).to_string());
+    }
+
+    #[test]
+    fn process_metadata_is_written_unless_the_log_contains_it() {
+        let metadata = ProcessMetadata::default().with_pid().with_hostname();
+        let sut = DatadogLog {
+            timestamp: timestamp!("2022-01-01T00:00:00Z"),
+            level: Level::INFO,
+            message: "Hello World!".to_string(),
+            fields: vec![],
+            target: "target".to_string(),
+            datadog_ids: None,
+            attributes: vec![("hostname".to_string(), "from-attributes".into())],
+            standard_attributes: false,
+            normalize_durations: false,
+            global_attributes: vec![("process.pid".to_string(), "global".into())],
+            process_metadata: Some(&metadata),
+        };
+
+        let log: Value = serde_json::from_str(&sut.format()).unwrap();
+
+        assert_that(log["hostname"].clone()).is(json!("from-attributes"));
+        assert_that(log["process.pid"].clone()).is(json!(std::process::id()));
+    }
+
+    #[test]
+    fn duplicate_fields_are_written_as_array() {
+        let fields = ["outer", "inner", "event"]
+            .into_iter()
+            .map(|value| {
+                FieldPair {
+                    name: "foo".to_string(),
+                    value: value.to_string(),
+                    nanos: None,
+                }
+            })
+            .collect();
+
+        let sut = DatadogLog {
+            timestamp: timestamp!("2022-01-01T00:00:00Z"),
+            level: Level::INFO,
+            message: "Hello World!".to_string(),
+            fields,
+            target: "target".to_string(),
+            datadog_ids: None,
+            attributes: vec![],
+            standard_attributes: false,
+            normalize_durations: false,
+            global_attributes: vec![],
+            process_metadata: None,
+        };
+
+        assert_that(sut.format()).is(json!({"timestamp": "2022-01-01T00:00:00+00:00", "level": "INFO", "fields.foo": ["outer", "inner", "event"], "message": "Hello World! foo=outer foo=inner foo=event", "target": "target"}).to_string());
+    }
+
+    #[test]
+    fn well_known_fields_are_written_as_standard_attributes() {
+        let fields = [
+            ("http.request.method", "\"GET\""),
+            ("http.response.status_code", "200"),
+            ("client.address", "\"10.0.0.1\""),
+            ("duration", "1500"),
+            ("client.port", "\"not a port\""),
+            ("foo", "\"bar\""),
+        ]
+        .into_iter()
+        .map(|(name, value)| {
+            FieldPair {
+                name: name.to_string(),
+                value: value.to_string(),
+                nanos: None,
+            }
+        })
+        .collect();
+
+        let sut = DatadogLog {
+            timestamp: timestamp!("2022-01-01T00:00:00Z"),
+            level: Level::INFO,
+            message: "Hello World!".to_string(),
+            fields,
+            target: "target".to_string(),
+            datadog_ids: None,
+            attributes: vec![],
+            standard_attributes: true,
+            normalize_durations: false,
+            global_attributes: vec![],
+            process_metadata: None,
+        };
+
+        assert_that(sut.format()).is(json!({"timestamp": "2022-01-01T00:00:00+00:00", "level": "INFO", "fields.client.port": "not a port", "fields.foo": "bar", "message": "Hello World! client.port=not a port foo=bar", "target": "target", "network.client.ip": "10.0.0.1", "duration": 1500, "http.method": "GET", "http.status_code": 200}).to_string());
+    }
+
+    #[test]
+    fn fields_mapped_to_the_same_standard_attribute_are_all_written() {
+        let fields = [
+            ("http.status_code", "200"),
+            ("http.status_code", "500"),
+            ("net.peer.ip", "\"10.0.0.2\""),
+            ("client.address", "\"10.0.0.1\""),
+        ]
+        .into_iter()
+        .map(|(name, value)| {
+            FieldPair {
+                name: name.to_string(),
+                value: value.to_string(),
+                nanos: None,
+            }
+        })
+        .collect();
+
+        let sut = DatadogLog {
+            timestamp: timestamp!("2022-01-01T00:00:00Z"),
+            level: Level::INFO,
+            message: "Hello World!".to_string(),
+            fields,
+            target: "target".to_string(),
+            datadog_ids: None,
+            attributes: vec![],
+            standard_attributes: true,
+            normalize_durations: false,
+            global_attributes: vec![],
+            process_metadata: None,
+        };
+
+        assert_that(sut.format()).is(json!({"timestamp": "2022-01-01T00:00:00+00:00", "level": "INFO", "message": "Hello World!", "target": "target", "network.client.ip": ["10.0.0.1", "10.0.0.2"], "http.status_code": [200, 500]}).to_string());
+    }
+
+    #[test]
+    fn values_of_the_wrong_type_are_not_written_as_standard_attributes() {
+        let sut = DatadogLog {
+            timestamp: timestamp!("2022-01-01T00:00:00Z"),
+            level: Level::INFO,
+            message: "Hello World!".to_string(),
+            fields: vec![FieldPair {
+                name: "duration".to_string(),
+                value: "\"12.3ms\"".to_string(),
+                nanos: None,
+            }],
+            target: "target".to_string(),
+            datadog_ids: None,
+            attributes: vec![],
+            standard_attributes: true,
+            normalize_durations: false,
+            global_attributes: vec![],
+            process_metadata: None,
+        };
+
+        assert_that(sut.format()).is(json!({"timestamp": "2022-01-01T00:00:00+00:00", "level": "INFO", "fields.duration": "12.3ms", "message": "Hello World! duration=12.3ms", "target": "target"}).to_string());
+    }
+
     #[test]
     fn with_field() {
         let fields = vec![FieldPair {
             name: "foo".to_string(),
             value: "bar".to_string(),
+            nanos: None,
         }];
 
         let sut = DatadogLog {
@@ -142,6 +454,11 @@ mod format {
             fields,
             target: "target".to_string(),
             datadog_ids: None,
+            attributes: vec![],
+            standard_attributes: false,
+            normalize_durations: false,
+            global_attributes: vec![],
+            process_metadata: None,
         };
 
         assert_that(sut.format()).is(json!({"timestamp": "2022-01-01T00:00:00+00:00", "level": "INFO", "fields.foo": "bar", "message": "Hello World! foo=bar", "target": "target"}).to_string());
@@ -153,14 +470,17 @@ mod format {
             FieldPair {
                 name: "a".to_string(),
                 value: "c".to_string(),
+                nanos: None,
             },
             FieldPair {
                 name: "b".to_string(),
                 value: "b".to_string(),
+                nanos: None,
             },
             FieldPair {
                 name: "c".to_string(),
                 value: "a".to_string(),
+                nanos: None,
             },
         ];
 
@@ -171,6 +491,11 @@ mod format {
             fields,
             target: "target".to_string(),
             datadog_ids: None,
+            attributes: vec![],
+            standard_attributes: false,
+            normalize_durations: false,
+            global_attributes: vec![],
+            process_metadata: None,
         };
 
         assert_that(sut.format()).is(json!({"timestamp": "2022-01-01T00:00:00+00:00", "level": "INFO", "fields.a": "c", "fields.b": "b", "fields.c": "a", "message": "Hello World! a=c b=b c=a", "target": "target"}).to_string());
@@ -0,0 +1,119 @@
+use std::env;
+
+/// Git metadata for the source code integration, written with the [`GlobalAttributes`](crate::GlobalAttributes)
+///
+/// Writes `git.commit.sha` and `git.repository_url`, which Datadog uses to link logs
+/// and error stack frames to the exact commit. Missing values are omitted.
+///
+/// The values can be read when the application starts via [`GitMetadata::from_env`],
+/// captured when it is compiled via [`git_metadata!`](crate::git_metadata!) or set explicitly.
+///
+/// # Example
+/// ```
+/// use datadog_formatting_layer::{DatadogFormattingLayer, GitMetadata, GlobalAttributes};
+///
+/// let layer = DatadogFormattingLayer::default().with_global_attributes(
+///     GlobalAttributes::default().with_git_metadata(
+///         &GitMetadata::default()
+///             .with_commit_sha("8f3a1c2d9e7b6a5f4e3d2c1b0a9f8e7d6c5b4a39")
+///             .with_repository_url("https://github.com/example/payments-api"),
+///     ),
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GitMetadata {
+    commit_sha: Option<String>,
+    repository_url: Option<String>,
+    as_tags: bool,
+}
+
+impl GitMetadata {
+    /// Reads `DD_GIT_COMMIT_SHA` and `DD_GIT_REPOSITORY_URL` from the environment
+    #[must_use]
+    pub fn from_env() -> Self {
+        Self::from_values(
+            env::var("DD_GIT_COMMIT_SHA").ok().as_deref(),
+            env::var("DD_GIT_REPOSITORY_URL").ok().as_deref(),
+        )
+    }
+
+    /// Used by [`git_metadata!`](crate::git_metadata!)
+    #[doc(hidden)]
+    #[must_use]
+    pub fn from_values(commit_sha: Option<&str>, repository_url: Option<&str>) -> Self {
+        Self {
+            commit_sha: commit_sha
+                .map(str::trim)
+                .filter(|sha| !sha.is_empty())
+                .map(Into::into),
+            repository_url: repository_url
+                .map(str::trim)
+                .filter(|url| !url.is_empty())
+                .map(Into::into),
+            as_tags: false,
+        }
+    }
+
+    /// Write this commit as `git.commit.sha`
+    #[must_use]
+    pub fn with_commit_sha(self, commit_sha: impl Into<String>) -> Self {
+        Self {
+            commit_sha: Some(commit_sha.into()),
+            ..self
+        }
+    }
+
+    /// Write this repository as `git.repository_url`
+    #[must_use]
+    pub fn with_repository_url(self, repository_url: impl Into<String>) -> Self {
+        Self {
+            repository_url: Some(repository_url.into()),
+            ..self
+        }
+    }
+
+    /// Write the metadata to `ddtags` instead of as attributes
+    #[must_use]
+    pub fn with_tags(self) -> Self {
+        Self {
+            as_tags: true,
+            ..self
+        }
+    }
+
+    pub(crate) const fn as_tags(&self) -> bool {
+        self.as_tags
+    }
+
+    pub(crate) fn collect(&self) -> Vec<(&'static str, String)> {
+        [
+            ("git.commit.sha", &self.commit_sha),
+            ("git.repository_url", &self.repository_url),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| value.clone().map(|present| (key, present)))
+        .collect()
+    }
+}
+
+/// Captures `DD_GIT_COMMIT_SHA` and `DD_GIT_REPOSITORY_URL` when the calling crate is compiled
+/// as [`GitMetadata`]
+///
+/// # Example
+/// ```
+/// use datadog_formatting_layer::{git_metadata, DatadogFormattingLayer, GlobalAttributes};
+///
+/// // e.g. built with `DD_GIT_COMMIT_SHA=$(git rev-parse HEAD) cargo build`
+/// let layer = DatadogFormattingLayer::default().with_global_attributes(
+///     GlobalAttributes::default().with_git_metadata(&git_metadata!().with_tags()),
+/// );
+/// ```
+#[macro_export]
+macro_rules! git_metadata {
+    () => {
+        $crate::GitMetadata::from_values(
+            ::core::option_env!("DD_GIT_COMMIT_SHA"),
+            ::core::option_env!("DD_GIT_REPOSITORY_URL"),
+        )
+    };
+}
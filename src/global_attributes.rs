@@ -0,0 +1,137 @@
+use crate::{git_metadata::GitMetadata, kubernetes_metadata::KubernetesMetadata};
+use serde_json::Value;
+use std::{fmt, sync::Arc};
+
+type DynamicAttribute = Arc<dyn Fn() -> Option<Value> + Send + Sync>;
+
+/// Attributes written on every log e.g. `ddsource`, `ddtags` or `host`
+///
+/// Static attributes are set once, dynamic ones are evaluated for every log
+/// e.g. to read the current tenant from a task-local.
+///
+/// These have the lowest precedence: the timestamp, level, message, target, fields, ids and
+/// all other attributes of a log always win. Dynamic attributes win over static ones.
+///
+/// # Example
+/// ```
+/// use datadog_formatting_layer::{DatadogFormattingLayer, GlobalAttributes};
+///
+/// let layer = DatadogFormattingLayer::default().with_global_attributes(
+///     GlobalAttributes::default()
+///         .with_attribute("ddsource", "rust")
+///         .with_attribute("service", "payments-api")
+///         .with_tag("team", "payments")
+///         .with_tag("region", "eu")
+///         .with_dynamic_attribute("tenant.id", || Some("acme".into())),
+/// );
+/// ```
+#[derive(Clone, Default)]
+pub struct GlobalAttributes {
+    static_attributes: Vec<(String, Value)>,
+    tags: Vec<String>,
+    dynamic_attributes: Vec<(String, DynamicAttribute)>,
+}
+
+impl GlobalAttributes {
+    /// Write the attribute with a constant value on every log
+    #[must_use]
+    pub fn with_attribute(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.static_attributes.push((key.into(), value.into()));
+        self
+    }
+
+    /// Add a tag to `ddtags` e.g. `team:payments`
+    ///
+    /// Tags are merged with a `ddtags` attribute instead of being overwritten by it.
+    #[must_use]
+    pub fn with_tag(mut self, key: impl AsRef<str>, value: impl AsRef<str>) -> Self {
+        self.tags
+            .push(format!("{}:{}", key.as_ref(), value.as_ref()));
+        self
+    }
+
+    /// Write the Kubernetes and container metadata, which is read once right away
+    ///
+    /// See [`KubernetesMetadata`] for the written attributes or tags.
+    #[must_use]
+    pub fn with_kubernetes_metadata(self, kubernetes_metadata: &KubernetesMetadata) -> Self {
+        self.with_metadata(kubernetes_metadata.collect(), kubernetes_metadata.as_tags())
+    }
+
+    /// Write the git metadata for the source code integration
+    ///
+    /// See [`GitMetadata`] for the written attributes or tags.
+    #[must_use]
+    pub fn with_git_metadata(self, git_metadata: &GitMetadata) -> Self {
+        self.with_metadata(git_metadata.collect(), git_metadata.as_tags())
+    }
+
+    fn with_metadata(self, metadata: Vec<(&'static str, String)>, as_tags: bool) -> Self {
+        metadata.into_iter().fold(self, |attributes, (key, value)| {
+            if as_tags {
+                attributes.with_tag(key, value)
+            } else {
+                attributes.with_attribute(key, value)
+            }
+        })
+    }
+
+    /// Write the attribute with the value returned by the closure, which is called for every log.
+    ///
+    /// The attribute is omitted if the closure returns `None`.
+    #[must_use]
+    pub fn with_dynamic_attribute(
+        mut self,
+        key: impl Into<String>,
+        value: impl Fn() -> Option<Value> + Send + Sync + 'static,
+    ) -> Self {
+        self.dynamic_attributes.push((key.into(), Arc::new(value)));
+        self
+    }
+
+    /// The attributes ordered by precedence, highest first
+    ///
+    /// `ddtags` set as attribute are merged with the tags into one comma-joined `ddtags`.
+    pub(crate) fn read(&self) -> Vec<(String, Value)> {
+        let dynamic = self
+            .dynamic_attributes
+            .iter()
+            .filter_map(|(key, provider)| provider().map(|value| (key.clone(), value)));
+
+        let mut tags = Vec::new();
+        let mut attributes: Vec<(String, Value)> = dynamic
+            .chain(self.static_attributes.iter().cloned())
+            .filter_map(|(key, value)| {
+                if let (Value::String(tag), "ddtags") = (&value, key.as_str()) {
+                    tags.push(tag.clone());
+                    return None;
+                }
+                Some((key, value))
+            })
+            .collect();
+
+        tags.extend(self.tags.iter().cloned());
+        tags.retain(|tag| !tag.is_empty());
+        if !tags.is_empty() {
+            attributes.push(("ddtags".to_string(), tags.join(",").into()));
+        }
+
+        attributes
+    }
+}
+
+impl fmt::Debug for GlobalAttributes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let dynamic_keys: Vec<&str> = self
+            .dynamic_attributes
+            .iter()
+            .map(|(key, _)| key.as_str())
+            .collect();
+
+        f.debug_struct("GlobalAttributes")
+            .field("static_attributes", &self.static_attributes)
+            .field("tags", &self.tags)
+            .field("dynamic_attributes", &dynamic_keys)
+            .finish()
+    }
+}
@@ -0,0 +1,210 @@
+use std::{env, fs, path::PathBuf};
+
+/// Kubernetes and container metadata, written with the [`GlobalAttributes`](crate::GlobalAttributes)
+///
+/// The values are read once when added to the global attributes:
+/// - `pod_name`, `kube_namespace`, `kube_node` and `kube_container_name`
+///   from the environment variables set via the downward API,
+///   by default `POD_NAME`, `POD_NAMESPACE`, `NODE_NAME` and `CONTAINER_NAME`
+/// - `container_id` from `/proc/self/cgroup`
+///
+/// Missing values are omitted.
+///
+/// # Example
+/// ```
+/// use datadog_formatting_layer::{DatadogFormattingLayer, GlobalAttributes, KubernetesMetadata};
+///
+/// // writes e.g. "ddtags": "pod_name:api-7d9f,kube_namespace:payments"
+/// let layer = DatadogFormattingLayer::default().with_global_attributes(
+///     GlobalAttributes::default().with_kubernetes_metadata(
+///         &KubernetesMetadata::default()
+///             .with_pod_name_var("MY_POD_NAME")
+///             .with_tags(),
+///     ),
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KubernetesMetadata {
+    pod_name_var: String,
+    namespace_var: String,
+    node_name_var: String,
+    container_name_var: String,
+    cgroup_path: PathBuf,
+    as_tags: bool,
+}
+
+impl Default for KubernetesMetadata {
+    fn default() -> Self {
+        Self {
+            pod_name_var: "POD_NAME".to_string(),
+            namespace_var: "POD_NAMESPACE".to_string(),
+            node_name_var: "NODE_NAME".to_string(),
+            container_name_var: "CONTAINER_NAME".to_string(),
+            cgroup_path: PathBuf::from("/proc/self/cgroup"),
+            as_tags: false,
+        }
+    }
+}
+
+impl KubernetesMetadata {
+    /// Read `pod_name` from this environment variable instead of `POD_NAME`
+    #[must_use]
+    pub fn with_pod_name_var(self, var: impl Into<String>) -> Self {
+        Self {
+            pod_name_var: var.into(),
+            ..self
+        }
+    }
+
+    /// Read `kube_namespace` from this environment variable instead of `POD_NAMESPACE`
+    #[must_use]
+    pub fn with_namespace_var(self, var: impl Into<String>) -> Self {
+        Self {
+            namespace_var: var.into(),
+            ..self
+        }
+    }
+
+    /// Read `kube_node` from this environment variable instead of `NODE_NAME`
+    #[must_use]
+    pub fn with_node_name_var(self, var: impl Into<String>) -> Self {
+        Self {
+            node_name_var: var.into(),
+            ..self
+        }
+    }
+
+    /// Read `kube_container_name` from this environment variable instead of `CONTAINER_NAME`
+    #[must_use]
+    pub fn with_container_name_var(self, var: impl Into<String>) -> Self {
+        Self {
+            container_name_var: var.into(),
+            ..self
+        }
+    }
+
+    /// Read `container_id` from this file instead of `/proc/self/cgroup`
+    #[must_use]
+    pub fn with_cgroup_path(self, path: impl Into<PathBuf>) -> Self {
+        Self {
+            cgroup_path: path.into(),
+            ..self
+        }
+    }
+
+    /// Write the metadata to `ddtags` instead of as attributes
+    #[must_use]
+    pub fn with_tags(self) -> Self {
+        Self {
+            as_tags: true,
+            ..self
+        }
+    }
+
+    pub(crate) const fn as_tags(&self) -> bool {
+        self.as_tags
+    }
+
+    /// Reads the metadata which is available
+    pub(crate) fn collect(&self) -> Vec<(&'static str, String)> {
+        self.collect_from(|var| env::var(var).ok())
+    }
+
+    /// Reads the metadata with the variables from this lookup
+    fn collect_from(&self, lookup: impl Fn(&str) -> Option<String>) -> Vec<(&'static str, String)> {
+        let vars = [
+            ("pod_name", &self.pod_name_var),
+            ("kube_namespace", &self.namespace_var),
+            ("kube_node", &self.node_name_var),
+            ("kube_container_name", &self.container_name_var),
+        ];
+
+        let container_id = fs::read_to_string(&self.cgroup_path)
+            .ok()
+            .and_then(|cgroup| container_id(&cgroup));
+
+        vars.into_iter()
+            .filter_map(|(key, var)| {
+                lookup(var)
+                    .filter(|value| !value.is_empty())
+                    .map(|value| (key, value))
+            })
+            .chain(container_id.map(|id| ("container_id", id)))
+            .collect()
+    }
+}
+
+/// Finds the container id in the content of a cgroup file
+///
+/// The id is the last 64 hex digits path segment e.g. of
+/// `0::/kubepods/burstable/pod<uid>/cri-containerd-<id>.scope` or `1:name=systemd:/docker/<id>`
+fn container_id(cgroup: &str) -> Option<String> {
+    cgroup.lines().find_map(|line| {
+        let segment = line.rsplit('/').next()?;
+        let unit = segment.strip_suffix(".scope").unwrap_or(segment);
+        let id = unit.rsplit('-').next()?;
+
+        (id.len() == 64 && id.chars().all(|digit| digit.is_ascii_hexdigit()))
+            .then(|| id.to_string())
+    })
+}
+
+#[cfg(test)]
+mod cgroup {
+    use super::{container_id, KubernetesMetadata};
+    use smoothy::prelude::*;
+    use std::{env, fs, process};
+
+    const ID: &str = "3f1d8c2b9a7e6d5c4b3a29180f7e6d5c4b3a29180f7e6d5c4b3a29180f7e6d5c";
+
+    #[test]
+    fn reads_cgroup_v1_docker() {
+        let cgroup = format!("12:pids:/docker/{ID}\n1:name=systemd:/docker/{ID}\n");
+        assert_that(container_id(&cgroup)).is(Some(ID.to_string()));
+    }
+
+    #[test]
+    fn reads_containerd_scopes() {
+        let cgroup =
+            format!("0::/kubepods.slice/kubepods-pod1234.slice/cri-containerd-{ID}.scope\n");
+        assert_that(container_id(&cgroup)).is(Some(ID.to_string()));
+    }
+
+    #[test]
+    fn ignores_cgroups_without_container() {
+        assert_that(container_id("0::/\n")).is(None);
+        assert_that(container_id(
+            "0::/user.slice/user-1000.slice/session-2.scope\n",
+        ))
+        .is(None);
+    }
+
+    #[test]
+    fn reads_the_variables_and_the_cgroup_file() {
+        let cgroup_path = env::temp_dir().join(format!(
+            "datadog-formatting-layer-{}-kubernetes-cgroup",
+            process::id()
+        ));
+        fs::write(&cgroup_path, format!("0::/kubepods/pod-1/{ID}\n"))
+            .expect("Failed to write cgroup file");
+
+        let metadata = KubernetesMetadata::default()
+            .with_pod_name_var("MY_POD")
+            .with_cgroup_path(&cgroup_path)
+            .collect_from(|var| {
+                match var {
+                    "MY_POD" => Some("api-7d9f".to_string()),
+                    "POD_NAMESPACE" => Some("payments".to_string()),
+                    "NODE_NAME" => Some(String::new()),
+                    _ => None,
+                }
+            });
+        fs::remove_file(&cgroup_path).expect("Failed to remove cgroup file");
+
+        assert_that(metadata).is(vec![
+            ("pod_name", "api-7d9f".to_string()),
+            ("kube_namespace", "payments".to_string()),
+            ("container_id", ID.to_string()),
+        ]);
+    }
+}
@@ -1,18 +1,57 @@
+#[cfg(feature = "otel-0_30")]
+use crate::otel::Otel030;
+#[cfg(feature = "otel-0_31")]
+use crate::otel::Otel031;
+#[cfg(any(feature = "otel-0_30", feature = "otel-0_31"))]
 use crate::{
-    datadog_ids,
+    baggage::BaggageAttributes,
+    error_status::OtelErrorStatus,
+    otel::{self, OtelVersion},
+    span_attributes::{self, OtelSpanAttributes, PendingSpan},
+};
+use crate::{
+    correlation::{CorrelationIds, CorrelationProvider, HexIdKeys},
     event_sink::{EventSink, StdoutSink},
+    field_policy::{FieldCollisions, SpanFieldInheritance},
+    field_rules::FieldRules,
     fields::{self, FieldPair, FieldStore},
     formatting::DatadogLog,
+    global_attributes::GlobalAttributes,
+    native_ids::WithSpanIds,
+    process_metadata::ProcessMetadata,
+    redaction::FieldRedaction,
+    scrubbing::ValueScrubbing,
+    sensitive,
+    span_stack::SpanStack,
+    span_summary::{SpanSummaries, SpanTimings},
+    DefaultCorrelation,
 };
 use chrono::Utc;
-use tracing::{span::Attributes, Event, Id, Subscriber};
+use serde_json::Value;
+#[cfg(any(feature = "otel-0_30", feature = "otel-0_31"))]
+use std::sync::OnceLock;
+use std::{any::TypeId, ptr, sync::Arc};
+#[cfg(any(feature = "otel-0_30", feature = "otel-0_31"))]
+use tracing::dispatcher::WeakDispatch;
+#[cfg(any(feature = "otel-0_30", feature = "otel-0_31"))]
+use tracing::Level;
+use tracing::{
+    span::{Attributes, Record},
+    Dispatch, Event, Id, Subscriber,
+};
+#[cfg(any(feature = "otel-0_30", feature = "otel-0_31"))]
+use tracing_subscriber::registry::SpanRef;
 use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
 
 /// The layer responsible for formatting tracing events in a way datadog can parse them
 #[non_exhaustive]
 #[derive(Debug, Clone)]
-pub struct DatadogFormattingLayer<Sink: EventSink + 'static> {
+pub struct DatadogFormattingLayer<Sink: EventSink + 'static, Correlation = DefaultCorrelation> {
     event_sink: Sink,
+    correlation: Correlation,
+    options: Options,
+    /// Set once the layer is added to a subscriber
+    span_ids: Option<WithSpanIds>,
 }
 
 impl<S: EventSink + 'static> DatadogFormattingLayer<S> {
@@ -26,7 +65,322 @@ impl<S: EventSink + 'static> DatadogFormattingLayer<S> {
     ///     DatadogFormattingLayer::with_sink(StdoutSink::default());
     /// ```
     pub const fn with_sink(sink: S) -> Self {
-        Self { event_sink: sink }
+        Self {
+            event_sink: sink,
+            correlation: DefaultCorrelation::new(),
+            options: Options::new(),
+            span_ids: None,
+        }
+    }
+}
+
+impl<S: EventSink + 'static, C: CorrelationProvider> DatadogFormattingLayer<S, C> {
+    /// Use a custom provider for the ids logs are correlated with
+    ///
+    /// # Example
+    /// ```
+    /// use datadog_formatting_layer::{DatadogFormattingLayer, NoCorrelation};
+    ///
+    /// // never write "dd.trace_id" and "dd.span_id"
+    /// let layer = DatadogFormattingLayer::default().with_correlation(NoCorrelation::new());
+    /// ```
+    pub fn with_correlation<P: CorrelationProvider>(
+        self,
+        correlation: P,
+    ) -> DatadogFormattingLayer<S, P> {
+        DatadogFormattingLayer {
+            event_sink: self.event_sink,
+            correlation,
+            options: self.options,
+            span_ids: self.span_ids,
+        }
+    }
+
+    /// Additionally emit the trace and span id as OpenTelemetry-style lowercase hex strings
+    ///
+    /// # Example
+    /// ```
+    /// use datadog_formatting_layer::{DatadogFormattingLayer, HexIdKeys};
+    ///
+    /// // adds "otel.trace_id" and "otel.span_id" next to "dd.trace_id" and "dd.span_id"
+    /// let layer = DatadogFormattingLayer::default().with_hex_ids(HexIdKeys::default());
+    /// ```
+    #[must_use]
+    pub fn with_hex_ids(mut self, keys: HexIdKeys) -> Self {
+        self.options.hex_id_keys = Some(keys);
+        self
+    }
+
+    /// Whether logs in traces which were not sampled e.g. dropped by the OpenTelemetry sampler
+    /// are correlated with their trace. Defaults to `true`.
+    ///
+    /// Disabling this avoids linking logs to traces which were never stored by Datadog.
+    ///
+    /// With `otel-0_30` (`tracing-opentelemetry` 0.31) the sampler of a span only runs once it is
+    /// closed or gets a child span. Until then, spans use the sampling decision of their parent
+    /// and root spans are treated as unsampled, even with `Sampler::AlwaysOn`.
+    /// Events directly in a root span therefore lose their correlation if this is disabled.
+    ///
+    /// # Example
+    /// ```
+    /// use datadog_formatting_layer::DatadogFormattingLayer;
+    ///
+    /// let layer = DatadogFormattingLayer::default().with_unsampled_trace_ids(false);
+    /// ```
+    #[must_use]
+    pub const fn with_unsampled_trace_ids(mut self, enabled: bool) -> Self {
+        self.options.unsampled_trace_ids = enabled;
+        self
+    }
+
+    /// Write the allow-listed OpenTelemetry baggage entries as log attributes
+    ///
+    /// # Example
+    /// ```
+    /// use datadog_formatting_layer::{BaggageAttributes, DatadogFormattingLayer};
+    ///
+    /// let layer =
+    ///     DatadogFormattingLayer::default().with_baggage(BaggageAttributes::new(["tenant.id"]));
+    /// ```
+    #[cfg(any(feature = "otel-0_30", feature = "otel-0_31"))]
+    #[must_use]
+    pub fn with_baggage(mut self, baggage: BaggageAttributes) -> Self {
+        self.options.baggage = Some(baggage);
+        self
+    }
+
+    /// Write the attributes of the OpenTelemetry span an event belongs to as log attributes
+    ///
+    /// With `otel-0_31` (`tracing-opentelemetry` 0.32) the attributes of a span can not be read,
+    /// so this only mirrors the span fields as top-level attributes. Attributes set via
+    /// `OpenTelemetrySpanExt::set_attribute` are only written with `otel-0_30`.
+    ///
+    /// # Example
+    /// ```
+    /// use datadog_formatting_layer::{DatadogFormattingLayer, OtelSpanAttributes};
+    ///
+    /// let layer = DatadogFormattingLayer::default()
+    ///     .with_otel_span_attributes(OtelSpanAttributes::default().with_name());
+    /// ```
+    #[cfg(any(feature = "otel-0_30", feature = "otel-0_31"))]
+    #[must_use]
+    pub const fn with_otel_span_attributes(mut self, span_attributes: OtelSpanAttributes) -> Self {
+        self.options.otel_span_attributes = Some(span_attributes);
+        self
+    }
+
+    /// From which spans logs inherit fields. Defaults to all spans in their scope.
+    ///
+    /// Span summaries always contain the fields of the closed span, only its parents are limited.
+    ///
+    /// # Example
+    /// ```
+    /// use datadog_formatting_layer::{DatadogFormattingLayer, SpanFieldInheritance};
+    ///
+    /// let layer = DatadogFormattingLayer::default()
+    ///     .with_span_field_inheritance(SpanFieldInheritance::Disabled);
+    /// ```
+    #[must_use]
+    pub const fn with_span_field_inheritance(mut self, inheritance: SpanFieldInheritance) -> Self {
+        self.options.field_inheritance = inheritance;
+        self
+    }
+
+    /// What happens if fields of a log have the same name. Defaults to the innermost one winning.
+    ///
+    /// # Example
+    /// ```
+    /// use datadog_formatting_layer::{DatadogFormattingLayer, FieldCollisions};
+    ///
+    /// let layer = DatadogFormattingLayer::default().with_field_collisions(FieldCollisions::KeepAll);
+    /// ```
+    #[must_use]
+    pub const fn with_field_collisions(mut self, collisions: FieldCollisions) -> Self {
+        self.options.field_collisions = collisions;
+        self
+    }
+
+    /// Write the spans a log happened in as structured data
+    ///
+    /// # Example
+    /// ```
+    /// use datadog_formatting_layer::{DatadogFormattingLayer, SpanStack};
+    ///
+    /// let layer = DatadogFormattingLayer::default()
+    ///     .with_span_stack(SpanStack::default().with_span_list().with_current_span());
+    /// ```
+    #[must_use]
+    pub const fn with_span_stack(mut self, span_stack: SpanStack) -> Self {
+        self.options.span_stack = Some(span_stack);
+        self
+    }
+
+    /// Write static and dynamic attributes like `ddsource` or `ddtags` on every log
+    ///
+    /// # Example
+    /// ```
+    /// use datadog_formatting_layer::{DatadogFormattingLayer, GlobalAttributes};
+    ///
+    /// let layer = DatadogFormattingLayer::default().with_global_attributes(
+    ///     GlobalAttributes::default()
+    ///         .with_attribute("ddsource", "rust")
+    ///         .with_tag("team", "payments"),
+    /// );
+    /// ```
+    #[must_use]
+    pub fn with_global_attributes(mut self, global_attributes: GlobalAttributes) -> Self {
+        self.options.global_attributes = Some(global_attributes);
+        self
+    }
+
+    /// Write which host, process and thread a log was written by
+    ///
+    /// # Example
+    /// ```
+    /// use datadog_formatting_layer::{DatadogFormattingLayer, ProcessMetadata};
+    ///
+    /// let layer = DatadogFormattingLayer::default()
+    ///     .with_process_metadata(ProcessMetadata::default().with_hostname().with_thread());
+    /// ```
+    #[must_use]
+    pub fn with_process_metadata(mut self, process_metadata: ProcessMetadata) -> Self {
+        self.options.process_metadata = Some(process_metadata);
+        self
+    }
+
+    /// Drop, rename or move fields e.g. to match the facets in Datadog
+    ///
+    /// # Example
+    /// ```
+    /// use datadog_formatting_layer::{DatadogFormattingLayer, FieldRules};
+    ///
+    /// let layer = DatadogFormattingLayer::default()
+    ///     .with_field_rules(FieldRules::default().with_moved_field("user_id", "usr.id"));
+    /// ```
+    #[must_use]
+    pub fn with_field_rules(mut self, field_rules: FieldRules) -> Self {
+        self.options.field_rules = Some(field_rules);
+        self
+    }
+
+    /// Whether well-known fields are written as Datadog standard attributes with the expected types.
+    /// Defaults to `false`.
+    ///
+    /// Maps e.g. the OpenTelemetry semantic conventions `http.request.method`,
+    /// `http.response.status_code`, `client.address` and `enduser.id` to
+    /// `http.method`, `http.status_code`, `network.client.ip` and `usr.id`.
+    /// Mapped fields are written instead of `fields.<field>` and not added to the message,
+    /// unless their value does not have the expected type e.g. a `duration` of `"12.3ms"`.
+    /// Like regular fields, multiple values of the same attribute e.g. of a span and an event with
+    /// [`FieldCollisions::KeepAll`] or of `client.address` and `net.peer.ip` are written as an array.
+    ///
+    /// # Example
+    /// ```
+    /// use datadog_formatting_layer::DatadogFormattingLayer;
+    ///
+    /// // writes e.g. "http.status_code": 200 instead of "fields.http.response.status_code": "200"
+    /// let layer = DatadogFormattingLayer::default().with_standard_attributes(true);
+    /// ```
+    #[must_use]
+    pub const fn with_standard_attributes(mut self, enabled: bool) -> Self {
+        self.options.standard_attributes = enabled;
+        self
+    }
+
+    /// Whether durations recorded as [`Nanos`](crate::Nanos)
+    /// e.g. `elapsed = ?Nanos(start.elapsed())` are written as integer nanoseconds,
+    /// Datadog's unit for durations. Defaults to `false`.
+    ///
+    /// The message still contains the readable duration e.g. `elapsed=12.3ms`.
+    ///
+    /// # Example
+    /// ```
+    /// use datadog_formatting_layer::DatadogFormattingLayer;
+    ///
+    /// // writes e.g. "fields.elapsed": 12300000 instead of "fields.elapsed": "12.3ms"
+    /// let layer = DatadogFormattingLayer::default().with_normalized_durations(true);
+    /// ```
+    #[must_use]
+    pub const fn with_normalized_durations(mut self, enabled: bool) -> Self {
+        self.options.normalize_durations = enabled;
+        self
+    }
+
+    /// Mask or drop fields with secrets like passwords or tokens
+    ///
+    /// # Example
+    /// ```
+    /// use datadog_formatting_layer::{DatadogFormattingLayer, FieldRedaction};
+    ///
+    /// let layer = DatadogFormattingLayer::default()
+    ///     .with_field_redaction(FieldRedaction::default().with_field("iban").with_drop());
+    /// ```
+    #[must_use]
+    pub fn with_field_redaction(mut self, field_redaction: FieldRedaction) -> Self {
+        self.options.field_redaction = Some(field_redaction);
+        self
+    }
+
+    /// Replace secrets like credit card numbers or tokens in the message and field values
+    ///
+    /// # Example
+    /// ```
+    /// use datadog_formatting_layer::{DatadogFormattingLayer, ValueScrubbing};
+    ///
+    /// let layer = DatadogFormattingLayer::default()
+    ///     .with_value_scrubbing(ValueScrubbing::default().with_builtin_rules().with_hash())
+    ///     .with_sensitive_hash_key(b"my-secret-key");
+    /// ```
+    #[must_use]
+    pub fn with_value_scrubbing(mut self, value_scrubbing: ValueScrubbing) -> Self {
+        self.options.value_scrubbing = Some(value_scrubbing);
+        self
+    }
+
+    /// Write [`Sensitive`](crate::Sensitive) values as HMAC-SHA256 with this key instead of masking them
+    ///
+    /// The key is also used for the hashes of [`ValueScrubbing::with_hash`].
+    ///
+    /// # Example
+    /// ```
+    /// use datadog_formatting_layer::DatadogFormattingLayer;
+    ///
+    /// let layer = DatadogFormattingLayer::default().with_sensitive_hash_key(b"my-secret-key");
+    /// ```
+    #[must_use]
+    pub fn with_sensitive_hash_key(mut self, key: impl AsRef<[u8]>) -> Self {
+        self.options.sensitive_hash_key = Some(Arc::from(key.as_ref()));
+        self
+    }
+
+    /// Write a summary log with the duration of every span once it is closed
+    ///
+    /// # Example
+    /// ```
+    /// use datadog_formatting_layer::{DatadogFormattingLayer, SpanSummaries};
+    ///
+    /// let layer = DatadogFormattingLayer::default().with_span_summaries(SpanSummaries::default());
+    /// ```
+    #[must_use]
+    pub const fn with_span_summaries(mut self, span_summaries: SpanSummaries) -> Self {
+        self.options.span_summaries = Some(span_summaries);
+        self
+    }
+
+    /// Mark the OpenTelemetry span as errored when an ERROR event is emitted in it
+    ///
+    /// # Example
+    /// ```
+    /// use datadog_formatting_layer::{DatadogFormattingLayer, OtelErrorStatus};
+    ///
+    /// let layer =
+    ///     DatadogFormattingLayer::default().with_otel_error_status(OtelErrorStatus::default());
+    /// ```
+    #[cfg(any(feature = "otel-0_30", feature = "otel-0_31"))]
+    #[must_use]
+    pub const fn with_otel_error_status(mut self, error_status: OtelErrorStatus) -> Self {
+        self.options.otel_error_status = Some(error_status);
+        self
     }
 }
 
@@ -36,27 +390,148 @@ impl Default for DatadogFormattingLayer<StdoutSink> {
     }
 }
 
-impl<S: Subscriber + for<'a> LookupSpan<'a>, Sink: EventSink + 'static> Layer<S>
-    for DatadogFormattingLayer<Sink>
+impl<S, Sink, C> Layer<S> for DatadogFormattingLayer<Sink, C>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    Sink: EventSink + 'static,
+    C: CorrelationProvider + 'static,
 {
+    fn on_layer(&mut self, _subscriber: &mut S) {
+        self.span_ids = Some(WithSpanIds::new::<S>());
+    }
+
+    // lets `NativeCorrelation` reach the span ids through the dispatch, like `tracing-opentelemetry` does
+    unsafe fn downcast_raw(&self, id: TypeId) -> Option<*const ()> {
+        if id == TypeId::of::<Self>() {
+            Some(ptr::from_ref(self).cast())
+        } else if id == TypeId::of::<WithSpanIds>() {
+            self.span_ids
+                .as_ref()
+                .map(|span_ids| ptr::from_ref(span_ids).cast())
+        } else {
+            None
+        }
+    }
+
+    fn on_register_dispatch(&self, subscriber: &Dispatch) {
+        self.correlation.on_register_dispatch(subscriber);
+
+        // the otel context can only be read with access to the subscriber
+        #[cfg(any(feature = "otel-0_30", feature = "otel-0_31"))]
+        let _already_registered = self.options.dispatch.set(subscriber.downgrade());
+    }
+
     fn on_new_span(&self, span_attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
         #[allow(clippy::expect_used)]
         let span = ctx.span(id).expect("Span not found, this is a bug");
 
         let mut extensions = span.extensions_mut();
 
-        let fields = fields::from_attributes(span_attrs);
+        let fields = self.options.read_fields(span.metadata().target(), || {
+            fields::from_attributes(span_attrs)
+        });
 
         // insert fields from new span e.g #[instrument(fields(hello = "world"))]
         if extensions.get_mut::<FieldStore>().is_none() {
             extensions.insert(FieldStore { fields });
         }
+
+        // only track the timings if they are needed
+        if self.options.span_summaries.is_some() && extensions.get_mut::<SpanTimings>().is_none() {
+            extensions.insert(SpanTimings::new());
+        }
+        drop(extensions);
+
+        self.correlation.on_new_span(span_attrs, id, &ctx);
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        #[allow(clippy::expect_used)]
+        let span = ctx.span(id).expect("Span not found, this is a bug");
+
+        let mut extensions = span.extensions_mut();
+
+        // update fields recorded after the span was created e.g. span.record("hello", "world")
+        if let Some(store) = extensions.get_mut::<FieldStore>() {
+            store.record(
+                self.options
+                    .read_fields(span.metadata().target(), || fields::from_record(values)),
+            );
+        }
+    }
+
+    fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(timings) = span.extensions_mut().get_mut::<SpanTimings>() {
+                timings.enter();
+            }
+        }
+    }
+
+    fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(timings) = span.extensions_mut().get_mut::<SpanTimings>() {
+                timings.exit();
+            }
+        }
     }
 
-    // IDEA: maybe an on record implementation is required here
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span_summaries) = &self.options.span_summaries else {
+            return;
+        };
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+
+        let timings = span.extensions().get::<SpanTimings>().copied();
+        let Some(summary_attributes) =
+            timings.and_then(|span_timings| span_summaries.attributes(span.name(), &span_timings))
+        else {
+            return;
+        };
+
+        let correlation_ids = self
+            .options
+            .correlate(self.correlation.span_correlation_ids(&span));
+
+        let (resolved_fields, namespaced_fields) = self.options.field_collisions.resolve(
+            fields::from_closed_span(&span, self.options.field_inheritance),
+            Vec::new(),
+        );
+
+        let mut attributes = self.options.hex_id_attributes(correlation_ids.as_ref());
+        attributes.extend(summary_attributes);
+        if let Some(span_stack) = &self.options.span_stack {
+            attributes.extend(span_stack.read(&span));
+        }
+        let (span_fields, moved_fields) = self
+            .options
+            .split_moved_fields(resolved_fields, &attributes);
+        attributes.extend(moved_fields);
+        attributes.extend(namespaced_fields);
+
+        let log = DatadogLog {
+            timestamp: Utc::now(),
+            level: span.metadata().level().to_owned(),
+            message: format!("{} closed", span.name()),
+            fields: span_fields,
+            target: span.metadata().target().to_string(),
+            datadog_ids: correlation_ids.as_ref().map(CorrelationIds::datadog_ids),
+            attributes,
+            global_attributes: self.options.global_attributes(),
+            process_metadata: self.options.process_metadata.as_ref(),
+            standard_attributes: self.options.standard_attributes,
+            normalize_durations: self.options.normalize_durations,
+        };
+
+        self.event_sink.write(log.format());
+    }
 
     fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
-        let event_fields = fields::from_event(event);
+        let event_fields = self
+            .options
+            .read_fields(event.metadata().target(), || fields::from_event(event));
 
         // find message if present in event fields
         let message = event_fields
@@ -65,14 +540,41 @@ impl<S: Subscriber + for<'a> LookupSpan<'a>, Sink: EventSink + 'static> Layer<S>
             .map(|pair| pair.value.clone())
             .unwrap_or_default();
 
-        let all_fields: Vec<FieldPair> = Vec::default()
-            .into_iter()
-            .chain(fields::from_spans(&ctx, event))
-            .chain(event_fields)
-            .collect();
-
         // look for datadog trace- and span-id
-        let datadog_ids = datadog_ids::read_from_context(&ctx);
+        let correlation_ids = self
+            .options
+            .correlate(self.correlation.correlation_ids(&ctx, event));
+
+        let mut attributes = self.options.hex_id_attributes(correlation_ids.as_ref());
+
+        #[cfg(any(feature = "otel-0_30", feature = "otel-0_31"))]
+        attributes.extend(
+            self.options
+                .read_otel_context(&ctx, event, &message, &event_fields),
+        );
+
+        #[cfg(any(feature = "otel-0_30", feature = "otel-0_31"))]
+        if let (Some(span_attributes), Some(span_ref)) =
+            (&self.options.otel_span_attributes, ctx.event_span(event))
+        {
+            attributes.extend(self.options.read_otel_span(span_attributes, &span_ref));
+        }
+
+        if let (Some(span_stack), Some(span_ref)) =
+            (&self.options.span_stack, ctx.event_span(event))
+        {
+            attributes.extend(span_stack.read(&span_ref));
+        }
+
+        let (resolved_fields, namespaced_fields) = self.options.field_collisions.resolve(
+            fields::from_spans(&ctx, event, self.options.field_inheritance),
+            event_fields,
+        );
+        let (all_fields, moved_fields) = self
+            .options
+            .split_moved_fields(resolved_fields, &attributes);
+        attributes.extend(moved_fields);
+        attributes.extend(namespaced_fields);
 
         let log = DatadogLog {
             timestamp: Utc::now(),
@@ -80,7 +582,12 @@ impl<S: Subscriber + for<'a> LookupSpan<'a>, Sink: EventSink + 'static> Layer<S>
             message,
             fields: all_fields,
             target: event.metadata().target().to_string(),
-            datadog_ids,
+            datadog_ids: correlation_ids.as_ref().map(CorrelationIds::datadog_ids),
+            attributes,
+            global_attributes: self.options.global_attributes(),
+            process_metadata: self.options.process_metadata.as_ref(),
+            standard_attributes: self.options.standard_attributes,
+            normalize_durations: self.options.normalize_durations,
         };
 
         let serialized_event = log.format();
@@ -88,3 +595,256 @@ impl<S: Subscriber + for<'a> LookupSpan<'a>, Sink: EventSink + 'static> Layer<S>
         self.event_sink.write(serialized_event);
     }
 }
+
+/// The settings of the layer which do not depend on its type parameters
+#[derive(Debug, Clone)]
+struct Options {
+    hex_id_keys: Option<HexIdKeys>,
+    unsampled_trace_ids: bool,
+    span_summaries: Option<SpanSummaries>,
+    span_stack: Option<SpanStack>,
+    global_attributes: Option<GlobalAttributes>,
+    process_metadata: Option<ProcessMetadata>,
+    field_redaction: Option<FieldRedaction>,
+    value_scrubbing: Option<ValueScrubbing>,
+    sensitive_hash_key: Option<Arc<[u8]>>,
+    field_inheritance: SpanFieldInheritance,
+    field_collisions: FieldCollisions,
+    field_rules: Option<FieldRules>,
+    standard_attributes: bool,
+    normalize_durations: bool,
+    #[cfg(any(feature = "otel-0_30", feature = "otel-0_31"))]
+    baggage: Option<BaggageAttributes>,
+    #[cfg(any(feature = "otel-0_30", feature = "otel-0_31"))]
+    otel_error_status: Option<OtelErrorStatus>,
+    #[cfg(any(feature = "otel-0_30", feature = "otel-0_31"))]
+    otel_span_attributes: Option<OtelSpanAttributes>,
+    #[cfg(any(feature = "otel-0_30", feature = "otel-0_31"))]
+    dispatch: OnceLock<WeakDispatch>,
+}
+
+impl Options {
+    const fn new() -> Self {
+        Self {
+            hex_id_keys: None,
+            unsampled_trace_ids: true,
+            span_summaries: None,
+            span_stack: None,
+            global_attributes: None,
+            process_metadata: None,
+            field_redaction: None,
+            value_scrubbing: None,
+            sensitive_hash_key: None,
+            field_inheritance: SpanFieldInheritance::All,
+            field_collisions: FieldCollisions::InnermostWins,
+            field_rules: None,
+            standard_attributes: false,
+            normalize_durations: false,
+            #[cfg(any(feature = "otel-0_30", feature = "otel-0_31"))]
+            baggage: None,
+            #[cfg(any(feature = "otel-0_30", feature = "otel-0_31"))]
+            otel_error_status: None,
+            #[cfg(any(feature = "otel-0_30", feature = "otel-0_31"))]
+            otel_span_attributes: None,
+            #[cfg(any(feature = "otel-0_30", feature = "otel-0_31"))]
+            dispatch: OnceLock::new(),
+        }
+    }
+
+    /// The ids a log is correlated with, dropping invalid and (if disabled) unsampled ones
+    fn correlate(&self, provided: Option<CorrelationIds>) -> Option<CorrelationIds> {
+        provided
+            .filter(CorrelationIds::is_valid)
+            .filter(|ids| self.unsampled_trace_ids || ids.is_sampled())
+    }
+
+    /// The current values of the global attributes if enabled
+    fn global_attributes(&self) -> Vec<(String, Value)> {
+        self.global_attributes
+            .as_ref()
+            .map(GlobalAttributes::read)
+            .unwrap_or_default()
+    }
+
+    /// Reads the fields of a span or event with the field rules, redaction and scrubbing applied if enabled
+    fn read_fields(&self, target: &str, read: impl FnOnce() -> Vec<FieldPair>) -> Vec<FieldPair> {
+        let fields = sensitive::with_hash_key(self.sensitive_hash_key.as_ref(), read);
+
+        // redact first, so renamed fields are still matched by their original name
+        let redacted = match &self.field_redaction {
+            Some(field_redaction) => field_redaction.redact(fields),
+            None => fields,
+        };
+
+        let ruled = match &self.field_rules {
+            Some(field_rules) => field_rules.apply(target, redacted),
+            None => redacted,
+        };
+
+        match &self.value_scrubbing {
+            Some(value_scrubbing) => {
+                ruled
+                    .into_iter()
+                    .map(|field| {
+                        FieldPair {
+                            value: value_scrubbing
+                                .scrub(field.value, self.sensitive_hash_key.as_deref()),
+                            ..field
+                        }
+                    })
+                    .collect()
+            }
+            None => ruled,
+        }
+    }
+
+    /// Splits the moved fields from the other ones if there are field rules,
+    /// keeping the ones which would overwrite the attributes of the log
+    fn split_moved_fields(
+        &self,
+        fields: Vec<FieldPair>,
+        attributes: &[(String, Value)],
+    ) -> (Vec<FieldPair>, Vec<(String, Value)>) {
+        match &self.field_rules {
+            Some(field_rules) => {
+                field_rules
+                    .split_moved(fields, |path| attributes.iter().any(|(key, _)| key == path))
+            }
+            None => (fields, Vec::new()),
+        }
+    }
+
+    /// The trace and span id as hex strings if enabled
+    fn hex_id_attributes(&self, correlated: Option<&CorrelationIds>) -> Vec<(String, Value)> {
+        self.hex_id_keys
+            .as_ref()
+            .zip(correlated)
+            .map(|(keys, ids)| keys.to_attributes(ids).into())
+            .unwrap_or_default()
+    }
+
+    /// Applies the settings which need the OpenTelemetry context of the event
+    /// and returns the resulting log attributes
+    #[cfg(any(feature = "otel-0_30", feature = "otel-0_31"))]
+    fn read_otel_context<S>(
+        &self,
+        ctx: &Context<'_, S>,
+        event: &Event<'_>,
+        message: &str,
+        event_fields: &[FieldPair],
+    ) -> Vec<(String, Value)>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        if self.baggage.is_none() && self.otel_error_status.is_none() {
+            return Vec::new();
+        }
+
+        let dispatch = self.dispatch.get().and_then(WeakDispatch::upgrade);
+        let mut attributes = Vec::new();
+        #[cfg(feature = "otel-0_31")]
+        attributes.extend(self.apply_otel_context::<Otel031, S>(
+            ctx,
+            event,
+            dispatch.as_ref(),
+            message,
+            event_fields,
+        ));
+        #[cfg(feature = "otel-0_30")]
+        attributes.extend(self.apply_otel_context::<Otel030, S>(
+            ctx,
+            event,
+            dispatch.as_ref(),
+            message,
+            event_fields,
+        ));
+        attributes
+    }
+
+    /// Applies the settings to the OpenTelemetry context of the event in one otel version
+    #[cfg(any(feature = "otel-0_30", feature = "otel-0_31"))]
+    fn apply_otel_context<V, S>(
+        &self,
+        ctx: &Context<'_, S>,
+        event: &Event<'_>,
+        dispatch: Option<&Dispatch>,
+        message: &str,
+        event_fields: &[FieldPair],
+    ) -> Vec<(String, Value)>
+    where
+        V: OtelVersion,
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        let Some(otel_context) = otel::read_otel_context::<V, S>(ctx, event, dispatch) else {
+            return Vec::new();
+        };
+
+        if let (Some(error_status), &Level::ERROR) =
+            (&self.otel_error_status, event.metadata().level())
+        {
+            error_status.record::<V, _>(
+                ctx.event_span(event).as_ref(),
+                &otel_context,
+                message,
+                event_fields,
+            );
+        }
+
+        self.baggage
+            .as_ref()
+            .map(|baggage| baggage.read::<V>(&otel_context))
+            .unwrap_or_default()
+    }
+
+    /// The attributes of the otel span of the first otel version tracking the span
+    #[cfg(any(feature = "otel-0_30", feature = "otel-0_31"))]
+    fn read_otel_span<'a, S: LookupSpan<'a>>(
+        &self,
+        span_attributes: &OtelSpanAttributes,
+        span_ref: &SpanRef<'a, S>,
+    ) -> Vec<(String, Value)> {
+        let dispatch = self.dispatch.get().and_then(WeakDispatch::upgrade);
+
+        #[cfg(feature = "otel-0_31")]
+        if let Some(pending_span) = Otel031::pending_span(span_ref, dispatch.as_ref()) {
+            return span_attributes.read(self.read_pending_span(span_ref, pending_span));
+        }
+        #[cfg(feature = "otel-0_30")]
+        if let Some(pending_span) = Otel030::pending_span(span_ref, dispatch.as_ref()) {
+            return span_attributes.read(self.read_pending_span(span_ref, pending_span));
+        }
+        Vec::new()
+    }
+
+    /// Applies the field rules, redaction and scrubbing to the attributes
+    /// like [`Self::read_fields`] does, as these are not read as span fields
+    #[cfg(any(feature = "otel-0_30", feature = "otel-0_31"))]
+    fn read_pending_span<'a, S: LookupSpan<'a>>(
+        &self,
+        span_ref: &SpanRef<'a, S>,
+        pending_span: PendingSpan,
+    ) -> PendingSpan {
+        let fields = pending_span
+            .attributes
+            .into_iter()
+            .map(|(name, value)| {
+                FieldPair {
+                    name,
+                    value: value.to_string(),
+                    nanos: None,
+                }
+            })
+            .collect();
+
+        let attributes = self
+            .read_fields(span_ref.metadata().target(), || fields)
+            .into_iter()
+            .map(|field| (field.name, span_attributes::attribute_value(&field.value)))
+            .collect();
+
+        PendingSpan {
+            attributes,
+            ..pending_span
+        }
+    }
+}
@@ -11,12 +11,75 @@
     )
 )]
 
+#[cfg(any(feature = "otel-0_30", feature = "otel-0_31"))]
+mod baggage;
+mod correlation;
+#[cfg(any(feature = "otel-0_30", feature = "otel-0_31"))]
 mod datadog_ids;
+mod durations;
+#[cfg(any(feature = "otel-0_30", feature = "otel-0_31"))]
+mod error_status;
 mod event_sink;
+mod field_policy;
+mod field_rules;
 mod fields;
 mod formatting;
+mod git_metadata;
+mod global_attributes;
+mod kubernetes_metadata;
 mod layer;
+mod native_ids;
+#[cfg(any(feature = "otel-0_30", feature = "otel-0_31"))]
+mod otel;
+mod process_metadata;
+pub mod propagation;
+mod redaction;
+mod scrubbing;
+mod sensitive;
+#[cfg(any(feature = "otel-0_30", feature = "otel-0_31"))]
+mod span_attributes;
+mod span_stack;
+mod span_summary;
+mod standard_attributes;
 
 // reexport
+#[cfg(any(feature = "otel-0_30", feature = "otel-0_31"))]
+pub use baggage::BaggageAttributes;
+pub use correlation::{
+    CorrelationIds, CorrelationProvider, HexIdKeys, NoCorrelation, TracingSpanCorrelation,
+};
+#[cfg(any(feature = "otel-0_30", feature = "otel-0_31"))]
+pub use datadog_ids::OtelCorrelation;
+pub use durations::Nanos;
+#[cfg(any(feature = "otel-0_30", feature = "otel-0_31"))]
+pub use error_status::OtelErrorStatus;
 pub use event_sink::{EventSink, StdoutSink};
+pub use field_policy::{FieldCollisions, SpanFieldInheritance};
+pub use field_rules::FieldRules;
+pub use git_metadata::GitMetadata;
+pub use global_attributes::GlobalAttributes;
+pub use kubernetes_metadata::KubernetesMetadata;
 pub use layer::DatadogFormattingLayer;
+pub use native_ids::{NativeCorrelation, SeedGuard};
+pub use process_metadata::ProcessMetadata;
+pub use redaction::FieldRedaction;
+pub use scrubbing::{InvalidScrubbingRule, ValueScrubbing};
+pub use sensitive::Sensitive;
+#[cfg(any(feature = "otel-0_30", feature = "otel-0_31"))]
+pub use span_attributes::OtelSpanAttributes;
+pub use span_stack::SpanStack;
+pub use span_summary::SpanSummaries;
+
+/// The correlation provider used by [`DatadogFormattingLayer::with_sink`]
+///
+/// This is [`OtelCorrelation`] if one of the `otel-*` features is enabled
+/// and [`NoCorrelation`] otherwise.
+#[cfg(any(feature = "otel-0_30", feature = "otel-0_31"))]
+pub type DefaultCorrelation = OtelCorrelation;
+
+/// The correlation provider used by [`DatadogFormattingLayer::with_sink`]
+///
+/// This is `OtelCorrelation` if one of the `otel-*` features is enabled
+/// and [`NoCorrelation`] otherwise.
+#[cfg(not(any(feature = "otel-0_30", feature = "otel-0_31")))]
+pub type DefaultCorrelation = NoCorrelation;
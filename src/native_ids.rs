@@ -0,0 +1,262 @@
+use crate::correlation::{CorrelationIds, CorrelationProvider};
+use std::{
+    cell::RefCell,
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    marker::PhantomData,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tracing::{span::Attributes, Dispatch, Event, Id, Span, Subscriber};
+use tracing_subscriber::{
+    layer::Context,
+    registry::{LookupSpan, SpanRef},
+};
+
+thread_local! {
+    /// The ids of the incoming context root spans are created in
+    static SEED: RefCell<Option<CorrelationIds>> = const { RefCell::new(None) };
+}
+
+/// Correlates logs using trace and span ids generated by the layer itself
+///
+/// This allows grouping the logs of one request by their trace without an OpenTelemetry pipeline.
+/// Every span gets a new random span id when it is created. Root spans also start a new trace,
+/// child spans inherit the trace id of their parent. The ids are stored in the span extensions.
+///
+/// To continue a trace of another service, the root span has to be created
+/// while its ids are seeded via [`NativeCorrelation::seed`]
+/// or its parent has to be set via [`NativeCorrelation::set_parent`].
+/// See [`propagation`](crate::propagation) for reading them from request headers.
+///
+/// # Example
+/// ```
+/// use datadog_formatting_layer::{CorrelationIds, DatadogFormattingLayer, NativeCorrelation};
+/// use tracing::info_span;
+/// use tracing_subscriber::prelude::*;
+///
+/// let subscriber = tracing_subscriber::registry().with(
+///     DatadogFormattingLayer::default()
+///         .with_correlation(NativeCorrelation::default().with_128_bit_trace_ids()),
+/// );
+/// let _guard = tracing::subscriber::set_default(subscriber);
+///
+/// // starts a new trace
+/// let job = info_span!("job");
+///
+/// // continues the trace of an incoming request
+/// let incoming = CorrelationIds::new(42, 7);
+/// let request = {
+///     let _seed = NativeCorrelation::seed(incoming);
+///     info_span!("request")
+/// };
+///
+/// let ids = request.in_scope(NativeCorrelation::current).unwrap();
+/// assert_eq!(ids.trace_id(), 42);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct NativeCorrelation {
+    trace_ids_128_bit: bool,
+}
+
+impl NativeCorrelation {
+    /// Create a provider generating 64-bit trace ids
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            trace_ids_128_bit: false,
+        }
+    }
+
+    /// Generate 128-bit trace ids instead of 64-bit ones.
+    ///
+    /// Like Datadog does, the upper 64 bits start with the creation time in unix seconds,
+    /// followed by 32 zero bits. `dd.trace_id` still contains the lower 64 bits only.
+    #[must_use]
+    pub const fn with_128_bit_trace_ids(self) -> Self {
+        Self {
+            trace_ids_128_bit: true,
+        }
+    }
+
+    /// Use the ids of an incoming context as parent of the root spans
+    /// created on the current thread until the guard is dropped.
+    ///
+    /// The root spans continue the trace of the context and
+    /// events outside of any span are correlated with the context itself.
+    #[must_use = "the ids are only seeded until the guard is dropped"]
+    pub fn seed(ids: CorrelationIds) -> SeedGuard {
+        let previous = SEED.replace(Some(ids));
+
+        SeedGuard {
+            previous,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Continue the trace of an incoming context in the span.
+    ///
+    /// The span takes over the trace id, sampling priority and propagated state of the context
+    /// and keeps its span id.
+    /// Has to be called before any child spans are created.
+    ///
+    /// Returns `false` if the span has no ids to continue the trace in, i.e. it is disabled
+    /// or its subscriber has no [`DatadogFormattingLayer`](crate::DatadogFormattingLayer)
+    /// using [`NativeCorrelation`].
+    #[must_use = "the trace is not continued if the span has no ids"]
+    pub fn set_parent(span: &Span, parent: CorrelationIds) -> bool {
+        with_span_ids(span, |ids| {
+            *ids = parent.with_span_id(ids.span_id());
+        })
+        .is_some()
+    }
+
+    /// The ids of the current span e.g. to propagate them to another service.
+    ///
+    /// Only works if the subscriber has a [`DatadogFormattingLayer`](crate::DatadogFormattingLayer)
+    /// using [`NativeCorrelation`].
+    #[must_use]
+    pub fn current() -> Option<CorrelationIds> {
+        with_span_ids(&Span::current(), |ids| ids.clone())
+    }
+
+    fn new_trace_id(&self) -> u128 {
+        let lower = u128::from(random_id());
+
+        if self.trace_ids_128_bit {
+            let seconds = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .ok()
+                .and_then(|duration| u32::try_from(duration.as_secs()).ok())
+                .unwrap_or_default();
+
+            (u128::from(seconds) << 96) | lower
+        } else {
+            lower
+        }
+    }
+}
+
+impl CorrelationProvider for NativeCorrelation {
+    fn on_new_span<S>(&self, attrs: &Attributes<'_>, id: &Id, ctx: &Context<'_, S>)
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        let parent = attrs.parent().map_or_else(
+            || {
+                attrs
+                    .is_contextual()
+                    .then(|| ctx.lookup_current())
+                    .flatten()
+            },
+            |parent_id| ctx.span(parent_id),
+        );
+
+        let parent_ids = parent
+            .and_then(|span_ref| span_ref.extensions().get::<CorrelationIds>().cloned())
+            .or_else(seeded_ids);
+
+        let ids = parent_ids.map_or_else(
+            || CorrelationIds::new(self.new_trace_id(), random_id()),
+            |parent| parent.with_span_id(random_id()),
+        );
+
+        if let Some(span_ref) = ctx.span(id) {
+            span_ref.extensions_mut().insert(ids);
+        }
+    }
+
+    fn correlation_ids<S>(&self, ctx: &Context<'_, S>, event: &Event<'_>) -> Option<CorrelationIds>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        ctx.event_span(event).map_or_else(
+            || event.is_contextual().then(seeded_ids).flatten(),
+            |span_ref| self.span_correlation_ids(&span_ref),
+        )
+    }
+
+    fn span_correlation_ids<'a, S>(&self, span_ref: &SpanRef<'a, S>) -> Option<CorrelationIds>
+    where
+        S: Subscriber + LookupSpan<'a>,
+    {
+        span_ref.extensions().get::<CorrelationIds>().cloned()
+    }
+}
+
+/// Seeds the ids of the root spans until it is dropped, see [`NativeCorrelation::seed`]
+#[derive(Debug)]
+pub struct SeedGuard {
+    previous: Option<CorrelationIds>,
+    // the seed is thread local
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for SeedGuard {
+    fn drop(&mut self) {
+        SEED.set(self.previous.take());
+    }
+}
+
+/// Looks up the ids of a span in the subscriber the layer was added to
+///
+/// The layer hands it out via `downcast_raw`, so the span extensions can be reached
+/// through the type-erased [`Dispatch`] whatever [`LookupSpan`] the subscriber is based on.
+#[derive(Debug, Clone, Copy)]
+pub struct WithSpanIds(fn(&Dispatch, &Id, ApplyToIds<'_>));
+
+type ApplyToIds<'a> = &'a mut dyn FnMut(&mut CorrelationIds);
+
+impl WithSpanIds {
+    pub fn new<S>() -> Self
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        Self(|dispatch, id, f| {
+            let found = dispatch
+                .downcast_ref::<S>()
+                .and_then(|subscriber| subscriber.span(id));
+            if let Some(span_ref) = found {
+                if let Some(ids) = span_ref.extensions_mut().get_mut::<CorrelationIds>() {
+                    f(ids);
+                }
+            }
+        })
+    }
+}
+
+/// Applies `f` to the ids stored in the extensions of the span
+fn with_span_ids<T>(span: &Span, f: impl FnOnce(&mut CorrelationIds) -> T) -> Option<T> {
+    span.with_subscriber(|(id, dispatch)| {
+        let lookup = dispatch.downcast_ref::<WithSpanIds>()?;
+
+        let mut pending = Some(f);
+        let mut result = None;
+        (lookup.0)(dispatch, id, &mut |ids| {
+            result = pending.take().map(|apply| apply(ids));
+        });
+        result
+    })
+    .flatten()
+}
+
+fn seeded_ids() -> Option<CorrelationIds> {
+    SEED.with_borrow(Clone::clone)
+}
+
+/// A random non-zero id
+fn random_id() -> u64 {
+    // every hasher of a new RandomState uses different keys,
+    // the counter makes sure the input differs as well
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    loop {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+
+        let id = hasher.finish();
+        if id != 0 {
+            return id;
+        }
+    }
+}
@@ -0,0 +1,79 @@
+//! The OpenTelemetry integration for every version enabled via the `otel-*` features
+//!
+//! The features are additive, so the version specific parts live behind [`OtelVersion`]
+//! and the rest of the layer is written once for all of them.
+
+#[cfg(feature = "otel-0_30")]
+mod v0_30;
+#[cfg(feature = "otel-0_31")]
+mod v0_31;
+
+use crate::{correlation::CorrelationIds, error_status::SpanError, span_attributes::PendingSpan};
+use tracing::{Dispatch, Event, Subscriber};
+use tracing_subscriber::{
+    layer::Context,
+    registry::{LookupSpan, SpanRef},
+};
+#[cfg(feature = "otel-0_30")]
+pub use v0_30::Otel030;
+#[cfg(feature = "otel-0_31")]
+pub use v0_31::Otel031;
+
+/// The parts of `opentelemetry` and `tracing-opentelemetry` which differ between their versions
+pub trait OtelVersion {
+    /// The OpenTelemetry context of this version
+    type Context;
+
+    /// The context of the otel span of a tracing span tracked by `tracing-opentelemetry`
+    fn span_context<'a, S: LookupSpan<'a>>(
+        span_ref: &SpanRef<'a, S>,
+        dispatch: Option<&Dispatch>,
+    ) -> Option<Self::Context>;
+
+    /// The active context e.g. attached via `Context::attach` by some middleware
+    fn current_context() -> Self::Context;
+
+    /// The ids of the span of the context, which are invalid if it has no span
+    fn correlation_ids(context: &Self::Context) -> CorrelationIds;
+
+    /// The value of a baggage entry of the context
+    fn baggage(context: &Self::Context, key: &str) -> Option<String>;
+
+    /// Records the error on the otel span of the tracing span or, if there is none, of the context
+    fn record_error<'a, S: LookupSpan<'a>>(
+        span_ref: Option<&SpanRef<'a, S>>,
+        context: &Self::Context,
+        error: SpanError,
+    );
+
+    /// The attributes, name and kind of the otel span of a tracing span tracked by `tracing-opentelemetry`
+    fn pending_span<'a, S: LookupSpan<'a>>(
+        span_ref: &SpanRef<'a, S>,
+        dispatch: Option<&Dispatch>,
+    ) -> Option<PendingSpan>;
+}
+
+/// Reads the OpenTelemetry context an event belongs to.
+///
+/// This is (in this order):
+/// 1. the context of the span of the event if it is tracked by `tracing-opentelemetry`
+///    and has valid ids, respecting explicit parents like the span fields do
+///    e.g. `info!(parent: &span, ...)`
+/// 2. the active context e.g. attached via `Context::attach` by some middleware,
+///    unless the event explicitly has no parent e.g. `info!(parent: None, ...)`
+pub fn read_otel_context<V, S>(
+    ctx: &Context<'_, S>,
+    event: &Event<'_>,
+    dispatch: Option<&Dispatch>,
+) -> Option<V::Context>
+where
+    V: OtelVersion,
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    let from_span = ctx
+        .event_span(event)
+        .and_then(|span_ref| V::span_context(&span_ref, dispatch))
+        .filter(|otel_context| V::correlation_ids(otel_context).is_valid());
+
+    from_span.or_else(|| event.is_contextual().then(V::current_context))
+}
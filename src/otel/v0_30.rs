@@ -0,0 +1,182 @@
+use super::OtelVersion;
+use crate::{correlation::CorrelationIds, error_status::SpanError, span_attributes::PendingSpan};
+use opentelemetry_0_30::{
+    baggage::BaggageExt,
+    trace::{
+        Event, SamplingDecision, SpanBuilder, SpanContext, Status, TraceContextExt, TraceFlags,
+    },
+    Context as OtelContext, KeyValue, Value as OtelValue,
+};
+use serde_json::Value;
+use std::time::SystemTime;
+use tracing::Dispatch;
+use tracing_opentelemetry_0_31::OtelData;
+use tracing_subscriber::registry::{LookupSpan, SpanRef};
+
+/// `opentelemetry` 0.30 and `tracing-opentelemetry` 0.31, enabled by the `otel-0_30` feature
+#[derive(Debug, Clone, Copy)]
+pub struct Otel030;
+
+impl OtelVersion for Otel030 {
+    type Context = OtelContext;
+
+    /// The otel span is only built once the tracing span is closed,
+    /// therefore its context is rebuilt from the pending span data.
+    /// If the sampler did not run yet, the sampling decision of the parent is used.
+    /// Root spans without a decision are treated as unsampled, as the sampler can not be run here.
+    fn span_context<'a, S: LookupSpan<'a>>(
+        span_ref: &SpanRef<'a, S>,
+        _dispatch: Option<&Dispatch>,
+    ) -> Option<OtelContext> {
+        span_ref
+            .extensions()
+            .get::<OtelData>()
+            .and_then(pending_otel_context)
+    }
+
+    fn current_context() -> OtelContext {
+        OtelContext::current()
+    }
+
+    fn correlation_ids(context: &OtelContext) -> CorrelationIds {
+        CorrelationIds::from(context.span().span_context())
+    }
+
+    fn baggage(context: &OtelContext, key: &str) -> Option<String> {
+        context
+            .baggage()
+            .get(key)
+            .map(|value| value.as_str().to_string())
+    }
+
+    fn record_error<'a, S: LookupSpan<'a>>(
+        span_ref: Option<&SpanRef<'a, S>>,
+        context: &OtelContext,
+        error: SpanError,
+    ) {
+        let status = Status::error(error.message);
+        let attributes = error
+            .attributes
+            .into_iter()
+            .map(|(key, value)| KeyValue::new(key, value));
+        let event = error.event.map(|event| {
+            let event_attributes = event
+                .attributes
+                .into_iter()
+                .map(|(key, value)| KeyValue::new(key, value))
+                .collect();
+            Event::new(event.name, SystemTime::now(), event_attributes, 0)
+        });
+
+        // tracing-opentelemetry 0.31 only builds the otel span once the tracing span is closed
+        if let Some(span) = span_ref {
+            if let Some(otel_data) = span.extensions_mut().get_mut::<OtelData>() {
+                record_pending(&mut otel_data.builder, status, attributes, event);
+                return;
+            }
+        }
+
+        let span = context.span();
+        span.set_status(status);
+        span.set_attributes(attributes);
+        if let Some(span_event) = event {
+            span.add_event(span_event.name, span_event.attributes);
+        }
+    }
+
+    /// All attributes of the pending otel span, including the ones
+    /// set via `OpenTelemetrySpanExt::set_attribute`
+    fn pending_span<'a, S: LookupSpan<'a>>(
+        span_ref: &SpanRef<'a, S>,
+        _dispatch: Option<&Dispatch>,
+    ) -> Option<PendingSpan> {
+        span_ref
+            .extensions()
+            .get::<OtelData>()
+            .map(|otel_data| pending_span(&otel_data.builder))
+    }
+}
+
+fn pending_otel_context(otel_data: &OtelData) -> Option<OtelContext> {
+    let parent_span = otel_data.parent_cx.span();
+    let parent = parent_span.span_context();
+
+    let trace_id = otel_data
+        .builder
+        .trace_id
+        .unwrap_or_else(|| parent.trace_id());
+    let span_id = otel_data.builder.span_id?;
+    let sampled = otel_data.builder.sampling_result.as_ref().map_or_else(
+        || parent.is_valid() && parent.is_sampled(),
+        |result| result.decision == SamplingDecision::RecordAndSample,
+    );
+    let trace_flags = if sampled {
+        TraceFlags::SAMPLED
+    } else {
+        TraceFlags::default()
+    };
+
+    let span_context = SpanContext::new(
+        trace_id,
+        span_id,
+        trace_flags,
+        false,
+        parent.trace_state().clone(),
+    );
+
+    Some(otel_data.parent_cx.with_remote_span_context(span_context))
+}
+
+fn pending_span(builder: &SpanBuilder) -> PendingSpan {
+    PendingSpan {
+        attributes: builder
+            .attributes
+            .iter()
+            .flatten()
+            .map(|attribute| (attribute.key.to_string(), to_json(&attribute.value)))
+            .collect(),
+        name: builder.name.to_string(),
+        kind: builder.span_kind.as_ref().map_or_else(
+            || "internal".to_string(),
+            |kind| format!("{kind:?}").to_lowercase(),
+        ),
+    }
+}
+
+fn record_pending(
+    builder: &mut SpanBuilder,
+    status: Status,
+    attributes: impl Iterator<Item = KeyValue>,
+    event: Option<Event>,
+) {
+    builder.status = status;
+    builder
+        .attributes
+        .get_or_insert_with(Vec::new)
+        .extend(attributes);
+    if let Some(span_event) = event {
+        builder.events.get_or_insert_with(Vec::new).push(span_event);
+    }
+}
+
+// strings, arrays and non-finite numbers are written as text,
+// other variants may be added in the future
+#[allow(clippy::wildcard_enum_match_arm)]
+fn to_json(value: &OtelValue) -> Value {
+    match value {
+        OtelValue::Bool(bool) => (*bool).into(),
+        OtelValue::I64(integer) => (*integer).into(),
+        OtelValue::F64(float) if float.is_finite() => (*float).into(),
+        other => other.as_str().into(),
+    }
+}
+
+impl From<&SpanContext> for CorrelationIds {
+    fn from(span_context: &SpanContext) -> Self {
+        Self::new(
+            u128::from_be_bytes(span_context.trace_id().to_bytes()),
+            u64::from_be_bytes(span_context.span_id().to_bytes()),
+        )
+        .with_sampled(span_context.is_sampled())
+    }
+}
@@ -0,0 +1,128 @@
+use super::OtelVersion;
+use crate::{
+    correlation::CorrelationIds,
+    error_status::SpanError,
+    fields::FieldStore,
+    span_attributes::{self, PendingSpan},
+};
+use opentelemetry::{
+    baggage::BaggageExt,
+    trace::{SpanContext, Status, TraceContextExt},
+    Context as OtelContext, KeyValue,
+};
+use tracing::Dispatch;
+use tracing_subscriber::registry::{LookupSpan, SpanRef};
+
+/// Fields with a special meaning for `tracing-opentelemetry` which are not span attributes
+const OTEL_SPECIAL_FIELDS: [&str; 4] = [
+    "otel.name",
+    "otel.kind",
+    "otel.status_code",
+    "otel.status_description",
+];
+
+/// `opentelemetry` 0.31 and `tracing-opentelemetry` 0.32, enabled by the `otel-0_31` feature
+#[derive(Debug, Clone, Copy)]
+pub struct Otel031;
+
+impl OtelVersion for Otel031 {
+    type Context = OtelContext;
+
+    fn span_context<'a, S: LookupSpan<'a>>(
+        span_ref: &SpanRef<'a, S>,
+        dispatch: Option<&Dispatch>,
+    ) -> Option<OtelContext> {
+        // this also starts the otel span if it was not entered yet
+        dispatch.and_then(|subscriber| {
+            tracing_opentelemetry::get_otel_context(&mut span_ref.extensions_mut(), subscriber)
+        })
+    }
+
+    fn current_context() -> OtelContext {
+        OtelContext::current()
+    }
+
+    fn correlation_ids(context: &OtelContext) -> CorrelationIds {
+        CorrelationIds::from(context.span().span_context())
+    }
+
+    fn baggage(context: &OtelContext, key: &str) -> Option<String> {
+        context
+            .baggage()
+            .get(key)
+            .map(|value| value.as_str().to_string())
+    }
+
+    fn record_error<'a, S: LookupSpan<'a>>(
+        _span_ref: Option<&SpanRef<'a, S>>,
+        context: &OtelContext,
+        error: SpanError,
+    ) {
+        let span = context.span();
+
+        span.set_status(Status::error(error.message));
+        span.set_attributes(
+            error
+                .attributes
+                .into_iter()
+                .map(|(key, value)| KeyValue::new(key, value)),
+        );
+        if let Some(event) = error.event {
+            let attributes = event
+                .attributes
+                .into_iter()
+                .map(|(key, value)| KeyValue::new(key, value))
+                .collect();
+            span.add_event(event.name, attributes);
+        }
+    }
+
+    /// `tracing-opentelemetry` 0.32 does not expose the attributes of a span,
+    /// therefore these are the span fields it turns into attributes.
+    /// Attributes set via `OpenTelemetrySpanExt::set_attribute` are missing.
+    fn pending_span<'a, S: LookupSpan<'a>>(
+        span_ref: &SpanRef<'a, S>,
+        dispatch: Option<&Dispatch>,
+    ) -> Option<PendingSpan> {
+        Self::span_context(span_ref, dispatch)?;
+
+        let fields = span_ref
+            .extensions()
+            .get::<FieldStore>()
+            .map(|store| store.fields.clone())
+            .unwrap_or_default();
+
+        let special_field = |name: &str| {
+            fields
+                .iter()
+                .find(|field| field.name == name)
+                .map(|field| field.value.trim_matches('\"').to_string())
+        };
+
+        Some(PendingSpan {
+            attributes: fields
+                .iter()
+                .filter(|field| !OTEL_SPECIAL_FIELDS.contains(&field.name.as_str()))
+                .map(|field| {
+                    (
+                        field.name.clone(),
+                        span_attributes::attribute_value(&field.value),
+                    )
+                })
+                .collect(),
+            name: special_field("otel.name").unwrap_or_else(|| span_ref.name().to_string()),
+            kind: special_field("otel.kind")
+                .map_or_else(|| "internal".to_string(), |kind| kind.to_lowercase()),
+        })
+    }
+}
+
+impl From<&SpanContext> for CorrelationIds {
+    fn from(span_context: &SpanContext) -> Self {
+        Self::new(
+            u128::from_be_bytes(span_context.trace_id().to_bytes()),
+            u64::from_be_bytes(span_context.span_id().to_bytes()),
+        )
+        .with_sampled(span_context.is_sampled())
+    }
+}
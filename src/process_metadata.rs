@@ -0,0 +1,132 @@
+use serde_json::Value;
+use std::{
+    env, fs, process,
+    sync::atomic::{AtomicU64, Ordering},
+    thread,
+};
+
+/// The id of the next thread writing its first log
+static NEXT_THREAD_ID: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    /// The name and id of the current thread as attributes serialized as JSON,
+    /// collected on its first log
+    static THREAD_ATTRIBUTES: Vec<(&'static str, String)> = {
+        // `ThreadId` has no stable numeric representation, so the ids are counted instead
+        let id = NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed);
+
+        thread::current()
+            .name()
+            .map(|name| ("logger.thread_name", Value::from(name)))
+            .into_iter()
+            .chain([("thread.id", Value::from(id))])
+            .map(|(key, value)| (key, value.to_string()))
+            .collect()
+    };
+}
+
+/// Writes which host, process and thread a log was written by
+///
+/// The host and process metadata does not change and is collected once when it is enabled,
+/// the thread metadata is collected once per thread.
+/// Both are kept serialized, so writing them does not allocate per log.
+///
+/// Writes (if enabled):
+/// - `hostname`: the `HOSTNAME` environment variable or the hostname of the system (Linux only)
+/// - `process.pid`: the id of the process
+/// - `process.executable.name`: the file name of the executable
+/// - `logger.thread_name` and `thread.id`: the name (if it has one) and id of the thread.
+///   The id is unique within the process and assigned in the order threads write their first log.
+///
+/// # Example
+/// ```
+/// use datadog_formatting_layer::{DatadogFormattingLayer, ProcessMetadata};
+///
+/// let layer = DatadogFormattingLayer::default().with_process_metadata(
+///     ProcessMetadata::default()
+///         .with_hostname()
+///         .with_pid()
+///         .with_executable()
+///         .with_thread(),
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProcessMetadata {
+    /// The enabled host and process attributes serialized as JSON
+    attributes: Vec<(&'static str, String)>,
+    thread: bool,
+}
+
+impl ProcessMetadata {
+    /// Write the hostname as `hostname`
+    #[must_use]
+    pub fn with_hostname(self) -> Self {
+        self.with_attribute("hostname", hostname())
+    }
+
+    /// Write the process id as `process.pid`
+    #[must_use]
+    pub fn with_pid(self) -> Self {
+        self.with_attribute("process.pid", Some(process::id()))
+    }
+
+    /// Write the file name of the executable as `process.executable.name`
+    #[must_use]
+    pub fn with_executable(self) -> Self {
+        let executable = env::current_exe().ok().and_then(|path| {
+            path.file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+        });
+
+        self.with_attribute("process.executable.name", executable)
+    }
+
+    /// Write the name and id of the thread as `logger.thread_name` and `thread.id`
+    #[must_use]
+    pub fn with_thread(self) -> Self {
+        Self {
+            thread: true,
+            ..self
+        }
+    }
+
+    /// Calls `write` with the key and serialized value of every enabled attribute
+    pub(crate) fn for_each(&self, mut write: impl FnMut(&'static str, &str)) {
+        for (key, value) in &self.attributes {
+            write(key, value);
+        }
+
+        if self.thread {
+            THREAD_ATTRIBUTES.with(|thread| {
+                for (key, value) in thread {
+                    write(key, value);
+                }
+            });
+        }
+    }
+
+    /// Whether the attribute is enabled
+    pub(crate) fn contains(&self, key: &str) -> bool {
+        self.attributes.iter().any(|(existing, _)| *existing == key)
+            || (self.thread
+                && THREAD_ATTRIBUTES
+                    .with(|thread| thread.iter().any(|(existing, _)| *existing == key)))
+    }
+
+    fn with_attribute(mut self, key: &'static str, value: Option<impl Into<Value>>) -> Self {
+        self.attributes.retain(|(existing, _)| *existing != key);
+        if let Some(present) = value {
+            self.attributes.push((key, present.into().to_string()));
+        }
+        self
+    }
+}
+
+fn hostname() -> Option<String> {
+    env::var("HOSTNAME")
+        .ok()
+        .or_else(|| fs::read_to_string("/proc/sys/kernel/hostname").ok())
+        .or_else(|| fs::read_to_string("/etc/hostname").ok())
+        .map(|hostname| hostname.trim().to_string())
+        .filter(|hostname| !hostname.is_empty())
+}
@@ -0,0 +1,575 @@
+//! Propagation of trace context via Datadog and W3C Trace Context headers
+//!
+//! This allows continuing traces across services without OpenTelemetry propagators.
+//! The extracted ids are attached to a span via
+//! [`NativeCorrelation::set_parent`](crate::NativeCorrelation::set_parent)
+//! or [`NativeCorrelation::seed`](crate::NativeCorrelation::seed).
+//!
+//! # Example
+//! ```
+//! use datadog_formatting_layer::{propagation, DatadogFormattingLayer, NativeCorrelation};
+//! use std::collections::HashMap;
+//! use tracing::info_span;
+//! use tracing_subscriber::prelude::*;
+//!
+//! let subscriber = tracing_subscriber::registry()
+//!     .with(DatadogFormattingLayer::default().with_correlation(NativeCorrelation::new()));
+//! let _guard = tracing::subscriber::set_default(subscriber);
+//!
+//! // incoming request
+//! let incoming = HashMap::from([
+//!     ("x-datadog-trace-id".to_string(), "42".to_string()),
+//!     ("x-datadog-parent-id".to_string(), "7".to_string()),
+//! ]);
+//!
+//! let span = info_span!("request");
+//! if let Some(parent) = propagation::extract(&incoming) {
+//!     assert!(NativeCorrelation::set_parent(&span, parent));
+//! }
+//!
+//! // outgoing request
+//! let mut outgoing = HashMap::new();
+//! if let Some(ids) = span.in_scope(NativeCorrelation::current) {
+//!     propagation::inject(&ids, &mut outgoing);
+//! }
+//!
+//! assert_eq!(outgoing["x-datadog-trace-id"], "42");
+//! ```
+
+use crate::correlation::CorrelationIds;
+use std::{collections::HashMap, hash::BuildHasher, iter};
+
+const DATADOG_TRACE_ID: &str = "x-datadog-trace-id";
+const DATADOG_PARENT_ID: &str = "x-datadog-parent-id";
+const DATADOG_SAMPLING_PRIORITY: &str = "x-datadog-sampling-priority";
+const DATADOG_TAGS: &str = "x-datadog-tags";
+const TRACEPARENT: &str = "traceparent";
+const TRACESTATE: &str = "tracestate";
+
+/// The prefix of the tags which are propagated with the trace
+const PROPAGATED_TAG_PREFIX: &str = "_dd.p.";
+/// The tag containing the upper 64 bits of a 128-bit trace id as hex
+const TRACE_ID_HIGH_TAG: &str = "_dd.p.tid";
+
+/// Reads headers of an incoming request
+///
+/// Header names are passed in lowercase, implementations should match them case-insensitively.
+pub trait Extractor {
+    /// The value of the header if present
+    fn get(&self, key: &str) -> Option<&str>;
+}
+
+/// Writes headers of an outgoing request
+///
+/// Header names are passed in lowercase.
+pub trait Injector {
+    /// Set the header to the value
+    fn set(&mut self, key: &str, value: String);
+}
+
+impl<S: BuildHasher> Extractor for HashMap<String, String, S> {
+    fn get(&self, key: &str) -> Option<&str> {
+        Self::get(self, key)
+            .or_else(|| {
+                self.iter()
+                    .find(|(name, _)| name.eq_ignore_ascii_case(key))
+                    .map(|(_, value)| value)
+            })
+            .map(String::as_str)
+    }
+}
+
+impl<S: BuildHasher> Injector for HashMap<String, String, S> {
+    fn set(&mut self, key: &str, value: String) {
+        self.insert(key.to_string(), value);
+    }
+}
+
+#[cfg(feature = "http")]
+impl Extractor for http::HeaderMap {
+    fn get(&self, key: &str) -> Option<&str> {
+        Self::get(self, key).and_then(|value| value.to_str().ok())
+    }
+}
+
+#[cfg(feature = "http")]
+impl Injector for http::HeaderMap {
+    fn set(&mut self, key: &str, value: String) {
+        if let (Ok(name), Ok(header_value)) = (
+            http::HeaderName::from_bytes(key.as_bytes()),
+            http::HeaderValue::from_str(&value),
+        ) {
+            self.insert(name, header_value);
+        }
+    }
+}
+
+/// The state of an incoming request which is not needed for correlation,
+/// but has to be passed on to the services called by it
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub(crate) struct PropagatedState {
+    /// The `_dd.p.*` tags except the upper bits of the trace id, which are derived from it
+    tags: Vec<(String, String)>,
+    /// The `tracestate` members of other vendors in their order
+    tracestate: Vec<String>,
+}
+
+/// Extract the ids of the calling span from the headers of an incoming request
+///
+/// The Datadog headers (`x-datadog-trace-id`, `x-datadog-parent-id`, `x-datadog-sampling-priority`
+/// and `x-datadog-tags`) take precedence over the W3C headers
+/// (`traceparent` and the `dd` member of `tracestate`).
+/// Returns `None` if neither contains valid ids.
+///
+/// The sampling priority, the `_dd.p.*` tags and the `tracestate` members of other vendors
+/// are kept, so [`inject`] passes them on to the called services.
+pub fn extract(headers: &impl Extractor) -> Option<CorrelationIds> {
+    let tracestate = headers.get(TRACESTATE).unwrap_or_default();
+
+    let valid = |extracted: &(CorrelationIds, _)| extracted.0.is_valid();
+    let (ids, tags) = extract_datadog(headers)
+        .filter(valid)
+        .or_else(|| extract_w3c(headers, tracestate).filter(valid))?;
+
+    let other_vendors: Vec<String> = tracestate
+        .split(',')
+        .map(str::trim)
+        .filter(|member| !member.is_empty() && !member.starts_with("dd="))
+        .map(ToString::to_string)
+        .collect();
+
+    let propagated = (!tags.is_empty() || !other_vendors.is_empty()).then_some(PropagatedState {
+        tags,
+        tracestate: other_vendors,
+    });
+
+    Some(ids.with_propagated(propagated))
+}
+
+/// Write the ids of the current span to the headers of an outgoing request
+///
+/// Both the Datadog and the W3C headers are written, so the called service can use either.
+/// The state of the incoming request is passed on as well.
+pub fn inject(ids: &CorrelationIds, headers: &mut impl Injector) {
+    let high = u64::try_from(ids.trace_id() >> 64).unwrap_or_default();
+    let low = u64::try_from(ids.trace_id() & u128::from(u64::MAX)).unwrap_or_default();
+    let priority = ids.sampling_priority();
+    let trace_flags = u8::from(ids.is_sampled());
+
+    let propagated = ids.propagated().cloned().unwrap_or_default();
+    let high_tag = (high != 0).then(|| (TRACE_ID_HIGH_TAG.to_string(), format!("{high:016x}")));
+    let tags: Vec<(String, String)> = high_tag.into_iter().chain(propagated.tags).collect();
+
+    headers.set(DATADOG_TRACE_ID, low.to_string());
+    headers.set(DATADOG_PARENT_ID, ids.span_id().to_string());
+    headers.set(DATADOG_SAMPLING_PRIORITY, priority.to_string());
+    if !tags.is_empty() {
+        let datadog_tags: Vec<String> = tags
+            .iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect();
+        headers.set(DATADOG_TAGS, datadog_tags.join(","));
+    }
+
+    headers.set(
+        TRACEPARENT,
+        format!(
+            "00-{:032x}-{:016x}-{trace_flags:02x}",
+            ids.trace_id(),
+            ids.span_id()
+        ),
+    );
+
+    // the trace id is already part of traceparent
+    let datadog_entries: Vec<String> = iter::once(format!("s:{priority}"))
+        .chain(
+            tags.iter()
+                .filter(|(key, _)| key != TRACE_ID_HIGH_TAG)
+                .filter_map(|(key, value)| {
+                    key.strip_prefix(PROPAGATED_TAG_PREFIX)
+                        .map(|name| format!("t.{name}:{}", value.replace('=', "~")))
+                }),
+        )
+        .collect();
+    let tracestate: Vec<String> = iter::once(format!("dd={}", datadog_entries.join(";")))
+        .chain(propagated.tracestate)
+        .collect();
+    headers.set(TRACESTATE, tracestate.join(","));
+}
+
+fn extract_datadog(headers: &impl Extractor) -> Option<(CorrelationIds, Vec<(String, String)>)> {
+    let low = headers.get(DATADOG_TRACE_ID)?.trim().parse::<u64>().ok()?;
+    let parent_id = headers.get(DATADOG_PARENT_ID)?.trim().parse::<u64>().ok()?;
+
+    let tags: Vec<(&str, &str)> = headers
+        .get(DATADOG_TAGS)
+        .map(|tags| {
+            tags.split(',')
+                .filter_map(|tag| tag.split_once('='))
+                .map(|(key, value)| (key.trim(), value.trim()))
+                .filter(|(key, _)| key.starts_with(PROPAGATED_TAG_PREFIX))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let high = tags
+        .iter()
+        .find(|(key, _)| *key == TRACE_ID_HIGH_TAG)
+        .and_then(|(_, value)| u64::from_str_radix(value, 16).ok())
+        .unwrap_or_default();
+
+    let sampling_priority = headers
+        .get(DATADOG_SAMPLING_PRIORITY)
+        .and_then(|priority| priority.trim().parse::<i8>().ok())
+        .unwrap_or(1);
+
+    let ids = CorrelationIds::new((u128::from(high) << 64) | u128::from(low), parent_id)
+        .with_sampling_priority(sampling_priority);
+
+    Some((ids, without_trace_id_tag(tags)))
+}
+
+fn extract_w3c(
+    headers: &impl Extractor,
+    tracestate: &str,
+) -> Option<(CorrelationIds, Vec<(String, String)>)> {
+    let mut parts = headers.get(TRACEPARENT)?.trim().split('-');
+
+    let version = parts.next().filter(|version| is_hex(version, 2))?;
+    let trace_id = parts.next().filter(|trace_id| is_hex(trace_id, 32))?;
+    let parent_id = parts.next().filter(|parent_id| is_hex(parent_id, 16))?;
+    let trace_flags = parts.next().filter(|flags| is_hex(flags, 2))?;
+
+    // version 00 has exactly 4 parts, later versions may append more
+    if version == "ff" || (version == "00" && parts.next().is_some()) {
+        return None;
+    }
+
+    let sampled_flag = u8::from_str_radix(trace_flags, 16).ok()? & 1 == 1;
+    let datadog_member = datadog_member(tracestate);
+
+    let sampling_priority = datadog_member
+        .clone()
+        .find(|(key, _)| *key == "s")
+        .and_then(|(_, priority)| priority.parse::<i8>().ok())
+        .unwrap_or_else(|| i8::from(sampled_flag));
+
+    // tags are written as e.g. `t.dm:-4` with `=` replaced by `~`
+    let tags = datadog_member
+        .filter_map(|(key, value)| {
+            key.strip_prefix("t.").map(|name| {
+                (
+                    format!("{PROPAGATED_TAG_PREFIX}{name}"),
+                    value.replace('~', "="),
+                )
+            })
+        })
+        .filter(|(key, _)| key != TRACE_ID_HIGH_TAG)
+        .collect();
+
+    let ids = CorrelationIds::new(
+        u128::from_str_radix(trace_id, 16).ok()?,
+        u64::from_str_radix(parent_id, 16).ok()?,
+    )
+    .with_sampling_priority(sampling_priority);
+
+    Some((ids, tags))
+}
+
+/// The entries of the `dd` member e.g. `dd=s:1;o:rum;t.dm:-4,other=value`
+fn datadog_member(tracestate: &str) -> impl Iterator<Item = (&str, &str)> + Clone {
+    tracestate
+        .split(',')
+        .filter_map(|member| member.split_once('='))
+        .find(|(key, _)| key.trim() == "dd")
+        .map(|(_, datadog)| datadog)
+        .unwrap_or_default()
+        .split(';')
+        .filter_map(|entry| entry.split_once(':'))
+}
+
+fn without_trace_id_tag(tags: Vec<(&str, &str)>) -> Vec<(String, String)> {
+    tags.into_iter()
+        .filter(|(key, _)| *key != TRACE_ID_HIGH_TAG)
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+/// Whether the value consists of exactly `len` lowercase hex digits
+fn is_hex(value: &str, len: usize) -> bool {
+    value.len() == len
+        && value
+            .chars()
+            .all(|char| char.is_ascii_digit() || ('a'..='f').contains(&char))
+}
+
+#[cfg(test)]
+mod headers {
+    use super::*;
+    use smoothy::prelude::*;
+
+    fn headers<const N: usize>(entries: [(&str, &str); N]) -> HashMap<String, String> {
+        entries
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn datadog_headers() {
+        let incoming = headers([
+            ("x-datadog-trace-id", "42"),
+            ("x-datadog-parent-id", "7"),
+            ("x-datadog-sampling-priority", "2"),
+        ]);
+
+        assert_that(extract(&incoming))
+            .is_some()
+            .and_value()
+            .is(CorrelationIds::new(42, 7).with_sampling_priority(2));
+    }
+
+    #[test]
+    fn header_names_are_matched_case_insensitively() {
+        let incoming = headers([("X-Datadog-Trace-Id", "42"), ("X-DATADOG-PARENT-ID", "7")]);
+
+        assert_that(extract(&incoming))
+            .is_some()
+            .and_value()
+            .is(CorrelationIds::new(42, 7));
+    }
+
+    #[test]
+    fn datadog_headers_with_128_bit_trace_id() {
+        let incoming = headers([
+            ("x-datadog-trace-id", "42"),
+            ("x-datadog-parent-id", "7"),
+            ("x-datadog-tags", "_dd.p.dm=-1,_dd.p.tid=640cfd8d00000000"),
+        ]);
+
+        assert_that(extract(&incoming))
+            .is_some()
+            .and_value()
+            .is(
+                CorrelationIds::new(0x640C_FD8D_0000_0000_0000_0000_0000_002A, 7).with_propagated(
+                    Some(PropagatedState {
+                        tags: vec![("_dd.p.dm".to_string(), "-1".to_string())],
+                        tracestate: vec![],
+                    }),
+                ),
+            );
+    }
+
+    #[test]
+    fn datadog_headers_rejected_by_sampling() {
+        let incoming = headers([
+            ("x-datadog-trace-id", "42"),
+            ("x-datadog-parent-id", "7"),
+            ("x-datadog-sampling-priority", "-1"),
+        ]);
+
+        assert_that(extract(&incoming))
+            .is_some()
+            .and_value()
+            .is(CorrelationIds::new(42, 7).with_sampling_priority(-1));
+    }
+
+    #[test]
+    fn w3c_headers() {
+        let incoming = headers([(
+            "traceparent",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        )]);
+
+        assert_that(extract(&incoming))
+            .is_some()
+            .and_value()
+            .is(CorrelationIds::new(
+                0x4BF9_2F35_77B3_4DA6_A3CE_929D_0E0E_4736,
+                0x00F0_67AA_0BA9_02B7,
+            ));
+    }
+
+    #[test]
+    fn w3c_headers_use_datadog_sampling_priority_of_tracestate() {
+        let incoming = headers([
+            (
+                "traceparent",
+                "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            ),
+            ("tracestate", "other=value,dd=o:rum;s:-1"),
+        ]);
+
+        assert_that(extract(&incoming).map(|ids| ids.sampling_priority()))
+            .is_some()
+            .and_value()
+            .is(-1);
+    }
+
+    #[test]
+    fn datadog_headers_take_precedence() {
+        let incoming = headers([
+            ("x-datadog-trace-id", "42"),
+            ("x-datadog-parent-id", "7"),
+            (
+                "traceparent",
+                "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            ),
+        ]);
+
+        assert_that(extract(&incoming))
+            .is_some()
+            .and_value()
+            .is(CorrelationIds::new(42, 7));
+    }
+
+    #[test]
+    fn invalid_datadog_headers_fall_back_to_w3c_headers() {
+        let incoming = headers([
+            ("x-datadog-trace-id", "0"),
+            ("x-datadog-parent-id", "7"),
+            (
+                "traceparent",
+                "00-0000000000000000000000000000002a-0000000000000007-01",
+            ),
+        ]);
+
+        assert_that(extract(&incoming))
+            .is_some()
+            .and_value()
+            .is(CorrelationIds::new(42, 7));
+    }
+
+    #[test]
+    fn invalid_headers() {
+        let invalid = [
+            headers([]),
+            headers([("x-datadog-trace-id", "42")]),
+            headers([("x-datadog-trace-id", "0"), ("x-datadog-parent-id", "7")]),
+            headers([("x-datadog-trace-id", "abc"), ("x-datadog-parent-id", "7")]),
+            headers([(
+                "traceparent",
+                "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            )]),
+            headers([(
+                "traceparent",
+                "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            )]),
+            headers([(
+                "traceparent",
+                "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            )]),
+            headers([(
+                "traceparent",
+                "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+            )]),
+        ];
+
+        for incoming in invalid {
+            assert_that(extract(&incoming)).is_none();
+        }
+    }
+
+    #[test]
+    fn injected_headers_can_be_extracted() {
+        let ids = CorrelationIds::new(0x640C_FD8D_0000_0000_0000_0000_0000_002A, 7);
+        let mut outgoing = HashMap::new();
+
+        inject(&ids, &mut outgoing);
+
+        assert_that(outgoing.clone()).is(headers([
+            ("x-datadog-trace-id", "42"),
+            ("x-datadog-parent-id", "7"),
+            ("x-datadog-sampling-priority", "1"),
+            ("x-datadog-tags", "_dd.p.tid=640cfd8d00000000"),
+            (
+                "traceparent",
+                "00-640cfd8d00000000000000000000002a-0000000000000007-01",
+            ),
+            ("tracestate", "dd=s:1"),
+        ]));
+        assert_that(extract(&outgoing))
+            .is_some()
+            .and_value()
+            .is(ids.clone());
+
+        outgoing.retain(|key, _| !key.starts_with("x-datadog"));
+        assert_that(extract(&outgoing))
+            .is_some()
+            .and_value()
+            .is(ids);
+    }
+
+    #[test]
+    fn injected_headers_keep_the_sampling_priority() {
+        for priority in [-1, 0, 1, 2] {
+            let ids = CorrelationIds::new(42, 7).with_sampling_priority(priority);
+            let mut outgoing = HashMap::new();
+
+            inject(&ids, &mut outgoing);
+
+            assert_that(outgoing[DATADOG_SAMPLING_PRIORITY].clone()).is(priority.to_string());
+            assert_that(outgoing[TRACESTATE].clone()).is(format!("dd=s:{priority}"));
+            assert_that(extract(&outgoing))
+                .is_some()
+                .and_value()
+                .is(ids.clone());
+
+            outgoing.retain(|key, _| !key.starts_with("x-datadog"));
+            assert_that(extract(&outgoing))
+                .is_some()
+                .and_value()
+                .is(ids);
+        }
+    }
+
+    #[test]
+    fn injected_headers_keep_the_state_of_the_incoming_request() {
+        let incoming = headers([
+            (
+                "traceparent",
+                "00-640cfd8d00000000000000000000002a-0000000000000007-01",
+            ),
+            (
+                "tracestate",
+                "dd=s:2;o:rum;t.dm:-4;t.usr.id:a~b,rojo=00f067aa0ba902b7,congo=t61rcWkgMzE",
+            ),
+        ]);
+        let mut outgoing = HashMap::new();
+
+        inject(&extract(&incoming).unwrap(), &mut outgoing);
+
+        assert_that(outgoing).is(headers([
+            ("x-datadog-trace-id", "42"),
+            ("x-datadog-parent-id", "7"),
+            ("x-datadog-sampling-priority", "2"),
+            (
+                "x-datadog-tags",
+                "_dd.p.tid=640cfd8d00000000,_dd.p.dm=-4,_dd.p.usr.id=a=b",
+            ),
+            (
+                "traceparent",
+                "00-640cfd8d00000000000000000000002a-0000000000000007-01",
+            ),
+            (
+                "tracestate",
+                "dd=s:2;t.dm:-4;t.usr.id:a~b,rojo=00f067aa0ba902b7,congo=t61rcWkgMzE",
+            ),
+        ]));
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn http_header_maps() {
+        let mut incoming = http::HeaderMap::new();
+        incoming.insert("X-Datadog-Trace-Id", "42".parse().unwrap());
+        incoming.insert("X-Datadog-Parent-Id", "7".parse().unwrap());
+
+        let ids = extract(&incoming).unwrap();
+        let mut outgoing = http::HeaderMap::new();
+        inject(&ids, &mut outgoing);
+
+        assert_that(ids).is(CorrelationIds::new(42, 7));
+        assert_that(outgoing.get("x-datadog-parent-id").cloned()).is(Some("7".parse().unwrap()));
+    }
+}
@@ -0,0 +1,149 @@
+use crate::fields::FieldPair;
+
+/// The fields redacted by default, matched case-insensitively
+const DEFAULT_FIELDS: [&str; 10] = [
+    "*password*",
+    "*passwd*",
+    "*secret*",
+    "*token*",
+    "*authorization*",
+    "*api_key*",
+    "*apikey*",
+    "*cookie*",
+    "*private_key*",
+    "*credential*",
+];
+
+pub const MASK: &str = "[REDACTED]";
+
+/// Masks or drops fields of events and spans by their name
+///
+/// Fields are matched case-insensitively by name or by a glob in which `*` matches anything,
+/// so secrets are found whatever their spelling.
+/// This differs from [`FieldRules`](crate::FieldRules), which match case-sensitively.
+/// By default common secret names like `password`, `token` or `authorization` are redacted.
+///
+/// Redacted fields are neither written as `fields.<field>` nor added to the message,
+/// the fields of spans are redacted when they are recorded. The `message` field is never redacted.
+///
+/// # Example
+/// ```
+/// use datadog_formatting_layer::{DatadogFormattingLayer, FieldRedaction};
+///
+/// // writes e.g. "fields.session_id": "[REDACTED]" and drops "fields.password"
+/// let layer = DatadogFormattingLayer::default()
+///     .with_field_redaction(FieldRedaction::default().with_field("session_*"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldRedaction {
+    patterns: Vec<String>,
+    drop: bool,
+}
+
+impl Default for FieldRedaction {
+    fn default() -> Self {
+        Self {
+            patterns: DEFAULT_FIELDS.iter().map(ToString::to_string).collect(),
+            drop: false,
+        }
+    }
+}
+
+impl FieldRedaction {
+    /// Also redact fields with this name or matching this glob e.g. `user.*`
+    #[must_use]
+    pub fn with_field(mut self, pattern: impl AsRef<str>) -> Self {
+        self.patterns.push(pattern.as_ref().to_lowercase());
+        self
+    }
+
+    /// Only redact the configured fields and not the common secret names
+    #[must_use]
+    pub fn without_default_fields(mut self) -> Self {
+        self.patterns
+            .retain(|pattern| !DEFAULT_FIELDS.contains(&pattern.as_str()));
+        self
+    }
+
+    /// Drop redacted fields instead of masking their value with `[REDACTED]`
+    #[must_use]
+    pub fn with_drop(self) -> Self {
+        Self { drop: true, ..self }
+    }
+
+    /// Masks or drops the matching fields
+    pub(crate) fn redact(&self, fields: Vec<FieldPair>) -> Vec<FieldPair> {
+        fields
+            .into_iter()
+            .filter_map(|field| {
+                if field.name == "message" || !self.matches(&field.name) {
+                    Some(field)
+                } else if self.drop {
+                    None
+                } else {
+                    Some(FieldPair {
+                        value: MASK.to_string(),
+                        nanos: None,
+                        ..field
+                    })
+                }
+            })
+            .collect()
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        let lowercase = name.to_lowercase();
+        self.patterns
+            .iter()
+            .any(|pattern| glob_matches(pattern, &lowercase))
+    }
+}
+
+/// Matches the name against a glob in which `*` matches any number of characters
+pub fn glob_matches(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+
+    let Some(last) = parts.next_back() else {
+        // no wildcard, the name must match exactly
+        return rest.is_empty();
+    };
+
+    for part in parts {
+        match rest.split_once(part) {
+            Some((_, after)) => rest = after,
+            None => return false,
+        }
+    }
+
+    rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod glob {
+    use super::glob_matches;
+    use smoothy::prelude::*;
+
+    #[test]
+    fn without_wildcard_the_name_must_match_exactly() {
+        assert_that(glob_matches("password", "password")).is(true);
+        assert_that(glob_matches("password", "password_hash")).is(false);
+        assert_that(glob_matches("password", "user_password")).is(false);
+    }
+
+    #[test]
+    fn wildcards_match_any_characters() {
+        assert_that(glob_matches("*token*", "token")).is(true);
+        assert_that(glob_matches("*token*", "access_token_id")).is(true);
+        assert_that(glob_matches("user.*", "user.email")).is(true);
+        assert_that(glob_matches("user.*", "username")).is(false);
+        assert_that(glob_matches("*.secret.*", "db.secret.key")).is(true);
+        assert_that(glob_matches("a*b*c", "abc")).is(true);
+        assert_that(glob_matches("a*b*c", "acb")).is(false);
+        assert_that(glob_matches("ab*ba", "aba")).is(false);
+    }
+}
@@ -0,0 +1,142 @@
+use crate::redaction::MASK;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::{
+    cell::RefCell,
+    fmt::{self, Debug, Display, Write},
+    sync::Arc,
+};
+
+thread_local! {
+    /// The key of the layer which is currently reading the fields of a span or event
+    static HASH_KEY: RefCell<Option<Arc<[u8]>>> = const { RefCell::new(None) };
+}
+
+/// Marks a value as sensitive, so it is never written to a log regardless of the field name
+///
+/// Its `Debug` and `Display` implementations always write `[REDACTED]`, also in other layers.
+/// If a key is configured via
+/// [`DatadogFormattingLayer::with_sensitive_hash_key`](crate::DatadogFormattingLayer::with_sensitive_hash_key),
+/// the layer writes `hmac:` and the start of the HMAC-SHA256 of the value in hex instead,
+/// so logs with the same value can still be found. The hash is built from the `Debug` output
+/// when recorded via `?` and from the `Display` output via `%`.
+///
+/// # Example
+/// ```
+/// use datadog_formatting_layer::Sensitive;
+/// use tracing::info;
+///
+/// let iban = Sensitive("DE89370400440532013000");
+///
+/// // writes "fields.account": "[REDACTED]"
+/// info!(account = ?iban, "Transferred");
+/// assert_eq!(format!("{iban}"), "[REDACTED]");
+/// ```
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Sensitive<T>(pub T);
+
+impl<T> Sensitive<T> {
+    /// The wrapped value
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> From<T> for Sensitive<T> {
+    fn from(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl<T: Debug> Debug for Sensitive<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        render(f, |value| write!(value, "{:?}", self.0))
+    }
+}
+
+impl<T: Display> Display for Sensitive<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        render(f, |value| write!(value, "{}", self.0))
+    }
+}
+
+/// Writes the mask or, while a layer with a key reads the fields, the hash of the value
+fn render(
+    f: &mut fmt::Formatter<'_>,
+    write_value: impl FnOnce(&mut String) -> fmt::Result,
+) -> fmt::Result {
+    match HASH_KEY.with_borrow(Clone::clone) {
+        Some(key) => {
+            let mut value = String::new();
+            write_value(&mut value)?;
+            f.write_str(&hash(&key, &value))
+        }
+        None => f.write_str(MASK),
+    }
+}
+
+/// `hmac:` and the start of the HMAC-SHA256 of the value in hex
+pub fn hash(key: &[u8], value: &str) -> String {
+    let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(key) else {
+        return MASK.to_string();
+    };
+    mac.update(value.as_bytes());
+
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .take(8)
+        .fold("hmac:".to_string(), |mut hash, byte| {
+            // writing to a string never fails
+            #[allow(clippy::expect_used)]
+            write!(hash, "{byte:02x}").expect("Failed to write hash");
+            hash
+        })
+}
+
+/// Reads the fields while sensitive values are hashed with the key or masked without one
+pub fn with_hash_key<T>(key: Option<&Arc<[u8]>>, read_fields: impl FnOnce() -> T) -> T {
+    let _reset = ResetHashKey(HASH_KEY.replace(key.cloned()));
+    read_fields()
+}
+
+/// Restores the previous key, even if reading the fields panics
+struct ResetHashKey(Option<Arc<[u8]>>);
+
+impl Drop for ResetHashKey {
+    fn drop(&mut self) {
+        HASH_KEY.set(self.0.take());
+    }
+}
+
+#[cfg(test)]
+mod rendering {
+    use super::{with_hash_key, Sensitive};
+    use smoothy::prelude::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn sensitive_values_are_masked() {
+        let value = Sensitive("secret");
+
+        assert_that(format!("{value:?}")).is("[REDACTED]".to_string());
+        assert_that(format!("{value}")).is("[REDACTED]".to_string());
+        assert_that(format!("{:?}", Some(value))).is("Some([REDACTED])".to_string());
+    }
+
+    #[test]
+    fn sensitive_values_are_hashed_with_the_key() {
+        let key: Arc<[u8]> = Arc::from(b"key".as_slice());
+        let other_key: Arc<[u8]> = Arc::from(b"other".as_slice());
+
+        let hashed = with_hash_key(Some(&key), || format!("{}", Sensitive("secret")));
+        let again = with_hash_key(Some(&key), || format!("{}", Sensitive("secret")));
+        let other = with_hash_key(Some(&other_key), || format!("{}", Sensitive("secret")));
+
+        assert_that(hashed.starts_with("hmac:")).is(true);
+        assert_that(hashed.len()).is(21);
+        assert_that(again).is(hashed.clone());
+        assert_that(other).is_not(hashed);
+        assert_that(format!("{}", Sensitive("secret"))).is("[REDACTED]".to_string());
+    }
+}
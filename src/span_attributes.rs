@@ -0,0 +1,93 @@
+use crate::formatting::is_core_key;
+use serde_json::Value;
+
+/// Copies the attributes of the OpenTelemetry span an event belongs to onto the log
+///
+/// The attributes are written under their attribute name e.g. `http.route`,
+/// so a log carries the same attributes as the trace does.
+/// The fields of the span are still written as `fields.<field>` as well.
+/// Only the attributes of the span itself are copied, not the ones of its parents.
+/// Attributes which would overwrite the keys the layer writes itself (`timestamp`, `level`,
+/// `message`, `target`, `dd.*` and `fields.*`) or the reserved attributes of Datadog
+/// (`host`, `service`, `status`, `env`, `version`, `ddsource` and `ddtags`) are skipped.
+/// Numbers and booleans keep their type, other values are written as text.
+/// Field rules, redaction and scrubbing apply to the attributes like they do to span fields.
+///
+/// Which attributes can be read depends on the `tracing-opentelemetry` version tracking the span:
+/// - 0.31 (`otel-0_30`): all attributes of the pending OpenTelemetry span, including the ones
+///   set via `OpenTelemetrySpanExt::set_attribute`
+/// - 0.32 (`otel-0_31`): `tracing-opentelemetry` does not expose the attributes of a span,
+///   therefore the span fields are mirrored as the attributes `tracing-opentelemetry` turns them
+///   into. Attributes set via `OpenTelemetrySpanExt::set_attribute` are **not** written.
+///
+/// # Example
+/// ```
+/// use datadog_formatting_layer::{DatadogFormattingLayer, OtelSpanAttributes};
+///
+/// // writes e.g. "http.route": "/users/{id}", "otel.name": "GET /users/{id}", "otel.kind": "server"
+/// let layer = DatadogFormattingLayer::default()
+///     .with_otel_span_attributes(OtelSpanAttributes::default().with_name().with_kind());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OtelSpanAttributes {
+    name: bool,
+    kind: bool,
+}
+
+impl OtelSpanAttributes {
+    /// Also write the span name as `otel.name`.
+    ///
+    /// Like `tracing-opentelemetry` does, this is the `otel.name` field if present
+    /// or the name of the span otherwise.
+    #[must_use]
+    pub const fn with_name(self) -> Self {
+        Self { name: true, ..self }
+    }
+
+    /// Also write the span kind as `otel.kind`.
+    ///
+    /// Like `tracing-opentelemetry` does, this is the `otel.kind` field if present
+    /// or `internal` otherwise.
+    #[must_use]
+    pub const fn with_kind(self) -> Self {
+        Self { kind: true, ..self }
+    }
+
+    /// The attributes of the span with its name and kind if enabled
+    pub(crate) fn read(&self, pending_span: PendingSpan) -> Vec<(String, Value)> {
+        let mut attributes: Vec<(String, Value)> = pending_span
+            .attributes
+            .into_iter()
+            .filter(|(key, _)| !is_core_key(key))
+            .collect();
+
+        if self.name {
+            attributes.push(("otel.name".to_string(), pending_span.name.into()));
+        }
+
+        if self.kind {
+            attributes.push(("otel.kind".to_string(), pending_span.kind.into()));
+        }
+
+        attributes
+    }
+}
+
+/// The attributes, name and kind of an OpenTelemetry span which was not built yet
+pub struct PendingSpan {
+    /// The attributes by their attribute name e.g. `http.route`
+    pub attributes: Vec<(String, Value)>,
+    /// The `otel.name` field or the name of the span
+    pub name: String,
+    /// The lowercase span kind e.g. `server`
+    pub kind: String,
+}
+
+/// The value `tracing-opentelemetry` records for a field value:
+/// numbers and booleans keep their type, everything else is text
+pub fn attribute_value(field_value: &str) -> Value {
+    match serde_json::from_str(field_value) {
+        Ok(value @ (Value::Bool(_) | Value::Number(_) | Value::String(_))) => value,
+        _ => Value::from(field_value.trim_matches('\"')),
+    }
+}
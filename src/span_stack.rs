@@ -0,0 +1,86 @@
+use crate::fields::FieldStore;
+use serde_json::{Map, Value};
+use tracing_subscriber::registry::{LookupSpan, SpanRef};
+
+/// Writes the spans a log happened in as structured data
+///
+/// Every span is written as an object with its `name`, `target` and `fields`:
+/// - `spans`: all spans of the log, starting at the root
+/// - `span`: the span the log belongs to
+///
+/// Unlike the `fields.<field>` attributes, the fields of the spans are not merged,
+/// so it is visible which span a field belongs to.
+///
+/// # Example
+/// ```
+/// use datadog_formatting_layer::{DatadogFormattingLayer, SpanStack};
+///
+/// // writes e.g. "spans": [{"name": "request", "target": "server", "fields": {"user": "Jack"}}]
+/// let layer =
+///     DatadogFormattingLayer::default().with_span_stack(SpanStack::default().with_span_list());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SpanStack {
+    list: bool,
+    current: bool,
+}
+
+impl SpanStack {
+    /// Write all spans of the log as `spans` array, starting at the root
+    #[must_use]
+    pub const fn with_span_list(self) -> Self {
+        Self { list: true, ..self }
+    }
+
+    /// Write the span the log belongs to as `span` object
+    #[must_use]
+    pub const fn with_current_span(self) -> Self {
+        Self {
+            current: true,
+            ..self
+        }
+    }
+
+    pub(crate) fn read<'a, S: LookupSpan<'a>>(
+        &self,
+        span_ref: &SpanRef<'a, S>,
+    ) -> Vec<(String, Value)> {
+        let mut attributes = Vec::new();
+
+        if self.list {
+            let spans: Vec<Value> = span_ref
+                .scope()
+                .from_root()
+                .map(|span| to_value(&span))
+                .collect();
+            attributes.push(("spans".to_string(), spans.into()));
+        }
+
+        if self.current {
+            attributes.push(("span".to_string(), to_value(span_ref)));
+        }
+
+        attributes
+    }
+}
+
+fn to_value<'a, S: LookupSpan<'a>>(span_ref: &SpanRef<'a, S>) -> Value {
+    let mut span_fields = span_ref
+        .extensions()
+        .get::<FieldStore>()
+        .map(|store| store.fields.clone())
+        .unwrap_or_default();
+    span_fields.sort();
+
+    let fields: Map<String, Value> = span_fields
+        .into_iter()
+        .map(|field| (field.name, field.value.trim_matches('\"').into()))
+        .collect();
+
+    let mut span = Map::new();
+    span.insert("name".to_string(), span_ref.name().into());
+    span.insert("target".to_string(), span_ref.metadata().target().into());
+    span.insert("fields".to_string(), fields.into());
+
+    span.into()
+}
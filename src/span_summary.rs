@@ -0,0 +1,99 @@
+use serde_json::Value;
+use std::time::{Duration, Instant};
+
+/// Writes a summary log for every span once it is closed
+///
+/// Spans without any events otherwise leave nothing in the logs.
+/// The summary has the level and target of the span, `<span name> closed` as message
+/// and contains the fields of the span as well as the ids it is correlated with.
+/// Additionally it contains (all in nanoseconds):
+/// - `duration`: the time from the creation of the span until it was closed,
+///   which is the standard attribute Datadog uses for latencies
+/// - `span.busy_ns`: the time the span was entered
+/// - `span.idle_ns`: the time the span existed without being entered
+///
+/// # Example
+/// ```
+/// use datadog_formatting_layer::{DatadogFormattingLayer, SpanSummaries};
+/// use std::time::Duration;
+///
+/// // only summarize spans which took at least 100ms
+/// let layer = DatadogFormattingLayer::default().with_span_summaries(
+///     SpanSummaries::default().with_min_duration(Duration::from_millis(100)),
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SpanSummaries {
+    min_duration: Duration,
+}
+
+impl SpanSummaries {
+    /// Only write summaries of spans which took at least this long
+    #[must_use]
+    pub const fn with_min_duration(self, min_duration: Duration) -> Self {
+        Self { min_duration }
+    }
+
+    /// The attributes of the summary or `None` if the span was too short
+    pub(crate) fn attributes(
+        &self,
+        span_name: &str,
+        timings: &SpanTimings,
+    ) -> Option<Vec<(String, Value)>> {
+        let duration = timings.created.elapsed();
+        if duration < self.min_duration {
+            return None;
+        }
+
+        let busy = timings.busy;
+        let idle = duration.saturating_sub(busy);
+
+        Some(vec![
+            ("span.name".to_string(), span_name.into()),
+            ("duration".to_string(), nanos(duration).into()),
+            ("span.busy_ns".to_string(), nanos(busy).into()),
+            ("span.idle_ns".to_string(), nanos(idle).into()),
+        ])
+    }
+}
+
+/// How long a span existed and was entered, stored in its extensions
+#[derive(Debug, Clone, Copy)]
+pub struct SpanTimings {
+    created: Instant,
+    busy: Duration,
+    entered: Option<Instant>,
+    // spans can be entered multiple times at once e.g. recursively
+    depth: usize,
+}
+
+impl SpanTimings {
+    pub fn new() -> Self {
+        Self {
+            created: Instant::now(),
+            busy: Duration::ZERO,
+            entered: None,
+            depth: 0,
+        }
+    }
+
+    pub fn enter(&mut self) {
+        if self.depth == 0 {
+            self.entered = Some(Instant::now());
+        }
+        self.depth = self.depth.saturating_add(1);
+    }
+
+    pub fn exit(&mut self) {
+        self.depth = self.depth.saturating_sub(1);
+        if self.depth == 0 {
+            if let Some(entered) = self.entered.take() {
+                self.busy = self.busy.saturating_add(entered.elapsed());
+            }
+        }
+    }
+}
+
+fn nanos(duration: Duration) -> u64 {
+    u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX)
+}
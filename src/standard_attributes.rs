@@ -0,0 +1,84 @@
+use serde_json::Value;
+
+/// The type Datadog expects for a standard attribute
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    String,
+    Integer,
+    Number,
+}
+
+/// Field names of tracing and the OpenTelemetry semantic conventions and their standard attribute in Datadog
+const MAPPINGS: [(&str, &str, Kind); 31] = [
+    // http
+    ("http.method", "http.method", Kind::String),
+    ("http.request.method", "http.method", Kind::String),
+    ("http.status_code", "http.status_code", Kind::Integer),
+    (
+        "http.response.status_code",
+        "http.status_code",
+        Kind::Integer,
+    ),
+    ("http.url", "http.url", Kind::String),
+    ("url.full", "http.url", Kind::String),
+    ("url.path", "http.url_details.path", Kind::String),
+    ("http.target", "http.url_details.path", Kind::String),
+    ("url.query", "http.url_details.queryString", Kind::String),
+    ("http.route", "http.route", Kind::String),
+    ("http.useragent", "http.useragent", Kind::String),
+    ("http.user_agent", "http.useragent", Kind::String),
+    ("user_agent.original", "http.useragent", Kind::String),
+    // network
+    ("network.client.ip", "network.client.ip", Kind::String),
+    ("client.address", "network.client.ip", Kind::String),
+    ("net.peer.ip", "network.client.ip", Kind::String),
+    ("network.client.port", "network.client.port", Kind::Integer),
+    ("client.port", "network.client.port", Kind::Integer),
+    // user
+    ("usr.id", "usr.id", Kind::String),
+    ("enduser.id", "usr.id", Kind::String),
+    // database
+    ("db.statement", "db.statement", Kind::String),
+    ("db.query.text", "db.statement", Kind::String),
+    ("db.operation", "db.operation", Kind::String),
+    ("db.operation.name", "db.operation", Kind::String),
+    ("db.name", "db.instance", Kind::String),
+    ("db.namespace", "db.instance", Kind::String),
+    // errors
+    ("error.type", "error.kind", Kind::String),
+    ("exception.type", "error.kind", Kind::String),
+    ("exception.message", "error.message", Kind::String),
+    ("exception.stacktrace", "error.stack", Kind::String),
+    // performance
+    ("duration", "duration", Kind::Number),
+];
+
+/// The standard attribute of the field with its value coerced to the expected type
+/// or `None` if there is no standard attribute for it.
+///
+/// Values which can not be coerced are `None` as well, so they are written as regular field
+/// instead of breaking the type of the attribute in Datadog.
+pub fn map(name: &str, value: &str) -> Option<(&'static str, Value)> {
+    let (_, attribute, kind) = MAPPINGS.iter().find(|(field, _, _)| *field == name)?;
+
+    let coerced = match kind {
+        Kind::String => Some(value.into()),
+        Kind::Integer => value.parse::<i64>().ok().map(Value::from),
+        Kind::Number => {
+            value
+                .parse::<u64>()
+                .ok()
+                .map(Value::from)
+                // infinity and NaN can not be written as JSON number
+                .or_else(|| {
+                    value
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|number| number.is_finite())
+                        .map(Value::from)
+                })
+        }
+    };
+
+    coerced.map(|coerced_value| (*attribute, coerced_value))
+}
@@ -0,0 +1,415 @@
+use crate::ObservableSink;
+use datadog_formatting_layer::{
+    DatadogFormattingLayer, FieldCollisions, FieldRedaction, FieldRules, Sensitive,
+    SpanFieldInheritance, SpanStack, SpanSummaries, ValueScrubbing,
+};
+use serde_json::{json, Value};
+use smoothy::prelude::*;
+use tracing::{dispatcher::DefaultGuard, info, info_span, instrument};
+use tracing_subscriber::prelude::*;
+
+#[test]
+fn innermost_field_wins_by_default() {
+    let (sink, _guard) = setup_fields_subscriber(|layer| layer);
+
+    log_with_colliding_fields();
+
+    let events = sink.events();
+    assert_that(&events).size().is(1);
+
+    assert_that(events[0].clone()).contains("\"fields.request\":\"42\",\"fields.user\":\"Event\",\"message\":\"Hello World! request=42 user=Event\"");
+}
+
+#[test]
+fn outermost_field_can_win() {
+    let (sink, _guard) = setup_fields_subscriber(|layer| {
+        layer.with_field_collisions(FieldCollisions::OutermostWins)
+    });
+
+    log_with_colliding_fields();
+
+    let events = sink.events();
+    assert_that(&events).size().is(1);
+
+    assert_that(events[0].clone()).contains("\"fields.request\":\"42\",\"fields.user\":\"Outer\",\"message\":\"Hello World! request=42 user=Outer\"");
+}
+
+#[test]
+fn colliding_fields_can_be_kept() {
+    let (sink, _guard) =
+        setup_fields_subscriber(|layer| layer.with_field_collisions(FieldCollisions::KeepAll));
+
+    log_with_colliding_fields();
+
+    let events = sink.events();
+    assert_that(&events).size().is(1);
+
+    assert_that(events[0].clone()).contains("\"fields.request\":\"42\",\"fields.user\":[\"Outer\",\"Inner\",\"Event\"],\"message\":\"Hello World! request=42 user=Outer user=Inner user=Event\"");
+}
+
+#[test]
+fn span_fields_can_be_namespaced_by_span_name() {
+    let (sink, _guard) = setup_fields_subscriber(|layer| {
+        layer.with_field_collisions(FieldCollisions::NamespaceBySpan)
+    });
+
+    log_with_colliding_fields();
+
+    let events = sink.events();
+    assert_that(&events).size().is(1);
+
+    assert_that(events[0].clone()).contains("\"fields.user\":\"Event\",\"message\":\"Hello World! user=Event\",\"target\":\"layer::fields\",\"span_fields.outer.request\":\"42\",\"span_fields.outer.user\":\"Outer\",\"span_fields.inner.user\":\"Inner\"}");
+}
+
+#[test]
+fn namespaced_fields_of_spans_with_the_same_name_are_kept() {
+    let (sink, _guard) = setup_fields_subscriber(|layer| {
+        layer.with_field_collisions(FieldCollisions::NamespaceBySpan)
+    });
+
+    info_span!("a", x = 1).in_scope(|| {
+        info_span!("a", x = 2).in_scope(|| info!("Hello World!"));
+    });
+
+    let events = sink.events();
+    assert_that(&events).size().is(1);
+
+    assert_that(events[0].clone()).contains("\"span_fields.a.x\":[\"1\",\"2\"]");
+}
+
+#[test]
+fn fields_can_be_inherited_from_the_nearest_spans_only() {
+    let (sink, _guard) = setup_fields_subscriber(|layer| {
+        layer.with_span_field_inheritance(SpanFieldInheritance::Nearest(1))
+    });
+
+    info_span!("outer", request = 42).in_scope(|| {
+        info_span!("inner", user = "Inner").in_scope(|| info!("Hello World!"));
+    });
+
+    let events = sink.events();
+    assert_that(&events).size().is(1);
+
+    assert_that(events[0].clone())
+        .contains("\"fields.user\":\"Inner\",\"message\":\"Hello World! user=Inner\"");
+    assert_that(events[0].contains("request")).is(false);
+}
+
+#[test]
+fn span_field_inheritance_can_be_disabled() {
+    let (sink, _guard) = setup_fields_subscriber(|layer| {
+        layer
+            .with_span_field_inheritance(SpanFieldInheritance::Disabled)
+            .with_span_summaries(SpanSummaries::default())
+    });
+
+    info_span!("span", user = "Span").in_scope(|| info!(id = 1, "Hello World!"));
+
+    let events = sink.events();
+    assert_that(&events).size().is(2);
+
+    assert_that(events[0].clone())
+        .contains("\"fields.id\":\"1\",\"message\":\"Hello World! id=1\"");
+    assert_that(events[0].contains("user")).is(false);
+    // summaries always have the fields of the closed span itself
+    assert_that(events[1].clone()).contains("\"message\":\"span closed user=Span\"");
+}
+
+#[test]
+fn span_stack_can_be_written() {
+    let (sink, _guard) = setup_fields_subscriber(|layer| {
+        layer.with_span_stack(SpanStack::default().with_span_list().with_current_span())
+    });
+
+    info!("Outside");
+    log_with_colliding_fields();
+
+    let events = sink.events();
+    assert_that(&events).size().is(2);
+
+    let outside: Value = serde_json::from_str(&events[0]).unwrap();
+    assert_that(outside.get("spans")).is_none();
+    assert_that(outside.get("span")).is_none();
+
+    let inner = json!({"name": "inner", "target": "layer::fields", "fields": {"user": "Inner"}});
+    let log: Value = serde_json::from_str(&events[1]).unwrap();
+    assert_that(log["spans"].clone()).is(json!([
+        {"name": "outer", "target": "layer::fields", "fields": {"request": "42", "user": "Outer"}},
+        inner,
+    ]));
+    assert_that(log["span"].clone()).is(inner);
+}
+
+#[test]
+fn span_stack_is_not_written_by_default() {
+    let (sink, _guard) = setup_fields_subscriber(|layer| layer);
+
+    log_with_colliding_fields();
+
+    let events = sink.events();
+    assert_that(&events).size().is(1);
+
+    assert_that(events[0].contains("\"spans\"")).is(false);
+    assert_that(events[0].contains("\"span\"")).is(false);
+}
+
+#[test]
+fn secret_fields_are_masked() {
+    let (sink, _guard) =
+        setup_fields_subscriber(|layer| layer.with_field_redaction(FieldRedaction::default()));
+
+    log_with_secrets();
+
+    let events = sink.events();
+    assert_that(&events).size().is(1);
+
+    assert_that(events[0].clone()).contains("\"fields.Authorization\":\"[REDACTED]\",\"fields.access_token\":\"[REDACTED]\",\"fields.password\":\"[REDACTED]\",\"fields.user\":\"Jack\",\"message\":\"Logged in Authorization=[REDACTED] access_token=[REDACTED] password=[REDACTED] user=Jack\"");
+    assert_that(events[0].contains("hunter2")).is(false);
+}
+
+#[test]
+fn configured_fields_can_be_dropped_without_the_defaults() {
+    let (sink, _guard) = setup_fields_subscriber(|layer| {
+        layer.with_field_redaction(
+            FieldRedaction::default()
+                .without_default_fields()
+                .with_field("pass*")
+                .with_field("user")
+                .with_drop(),
+        )
+    });
+
+    log_with_secrets();
+
+    let events = sink.events();
+    assert_that(&events).size().is(1);
+
+    assert_that(events[0].clone()).contains("\"fields.Authorization\":\"Bearer abc\",\"fields.access_token\":\"xyz\",\"message\":\"Logged in Authorization=Bearer abc access_token=xyz\"");
+    assert_that(events[0].contains("hunter2")).is(false);
+    assert_that(events[0].contains("Jack")).is(false);
+}
+
+#[test]
+fn fields_are_not_redacted_by_default() {
+    let (sink, _guard) = setup_fields_subscriber(|layer| layer);
+
+    log_with_secrets();
+
+    let events = sink.events();
+    assert_that(&events).size().is(1);
+
+    assert_that(events[0].clone()).contains("\"fields.password\":\"hunter2\"");
+}
+
+#[test]
+fn secrets_in_messages_and_field_values_are_scrubbed() {
+    let (sink, _guard) = setup_fields_subscriber(|layer| {
+        layer.with_value_scrubbing(
+            ValueScrubbing::default()
+                .with_builtin_rules()
+                .with_rule(r"api_key=(?P<secret>\w+)")
+                .unwrap(),
+        )
+    });
+
+    let url = "https://api.example.com?api_key=abc123";
+    info_span!("request", user = "jack@example.com").in_scope(|| {
+        info!(card = "4111-1111-1111-1111", "calling {url}");
+    });
+
+    let events = sink.events();
+    assert_that(&events).size().is(1);
+
+    assert_that(events[0].clone()).contains("\"fields.card\":\"[REDACTED]\",\"fields.user\":\"[REDACTED]\",\"message\":\"calling https://api.example.com?api_key=[REDACTED] card=[REDACTED] user=[REDACTED]\"");
+}
+
+#[test]
+fn sensitive_values_are_masked_regardless_of_the_field_name() {
+    let (sink, _guard) = setup_fields_subscriber(|layer| layer);
+
+    info_span!("transfer", account = ?Sensitive("DE89370400440532013000")).in_scope(|| {
+        info!(value = %Sensitive(42), "Transferred");
+    });
+
+    let events = sink.events();
+    assert_that(&events).size().is(1);
+
+    assert_that(events[0].clone()).contains("\"fields.account\":\"[REDACTED]\",\"fields.value\":\"[REDACTED]\",\"message\":\"Transferred account=[REDACTED] value=[REDACTED]\"");
+}
+
+#[test]
+fn sensitive_values_can_be_hashed() {
+    let (sink, _guard) = setup_fields_subscriber(|layer| layer.with_sensitive_hash_key("key"));
+
+    info!(first = %Sensitive("DE89"), second = %Sensitive("DE89"), other = %Sensitive("FR76"));
+
+    let events = sink.events();
+    assert_that(&events).size().is(1);
+
+    let log: Value = serde_json::from_str(&events[0]).unwrap();
+    let first = log["fields.first"].as_str().unwrap();
+    assert_that(first.starts_with("hmac:")).is(true);
+    assert_that(log["fields.second"].as_str().unwrap()).is(first);
+    assert_that(log["fields.other"].as_str().unwrap()).is_not(first);
+    assert_that(events[0].contains("DE89")).is(false);
+}
+
+#[test]
+fn scrubbed_secrets_are_hashed_with_the_sensitive_hash_key() {
+    let (sink, _guard) = setup_fields_subscriber(|layer| {
+        layer
+            .with_value_scrubbing(ValueScrubbing::default().with_emails().with_hash())
+            .with_sensitive_hash_key("key")
+    });
+
+    info!(user = "jack@example.com", account = %Sensitive("jack@example.com"));
+
+    let events = sink.events();
+    assert_that(&events).size().is(1);
+
+    let log: Value = serde_json::from_str(&events[0]).unwrap();
+    let user = log["fields.user"].as_str().unwrap();
+    assert_that(user.starts_with("hmac:")).is(true);
+    assert_that(log["fields.account"].as_str().unwrap()).is(user);
+}
+
+#[test]
+fn fields_can_be_denied_by_name_and_target() {
+    let (sink, _guard) = setup_fields_subscriber(|layer| {
+        layer.with_field_rules(
+            FieldRules::default()
+                .with_denied_target("noisy")
+                .with_denied_field("*_internal"),
+        )
+    });
+
+    info_span!(target: "noisy::client", "connect", connection = 7).in_scope(|| {
+        info!(user = "Jack", cache_internal = true, "Hello World!");
+        info!(target: "noisy", retries = 3, "Retrying");
+    });
+
+    let events = sink.events();
+    assert_that(&events).size().is(2);
+
+    assert_that(events[0].clone())
+        .contains("\"fields.user\":\"Jack\",\"message\":\"Hello World! user=Jack\"");
+    assert_that(events[0].contains("connection")).is(false);
+    assert_that(events[0].contains("cache_internal")).is(false);
+    assert_that(events[1].clone()).contains("\"message\":\"Retrying\",\"target\":\"noisy\"");
+}
+
+#[test]
+fn only_allowed_fields_are_kept() {
+    let (sink, _guard) = setup_fields_subscriber(|layer| {
+        layer.with_field_rules(FieldRules::default().with_allowed_field("req*"))
+    });
+
+    log_with_colliding_fields();
+
+    let events = sink.events();
+    assert_that(&events).size().is(1);
+
+    assert_that(events[0].clone())
+        .contains("\"fields.request\":\"42\",\"message\":\"Hello World! request=42\",");
+    assert_that(events[0].contains("user")).is(false);
+}
+
+#[test]
+fn fields_can_be_renamed_and_moved() {
+    let (sink, _guard) = setup_fields_subscriber(|layer| {
+        layer.with_field_rules(
+            FieldRules::default()
+                .with_renamed_field("request", "request_id")
+                .with_moved_field("user", "usr.id"),
+        )
+    });
+
+    log_with_colliding_fields();
+
+    let events = sink.events();
+    assert_that(&events).size().is(1);
+
+    assert_that(events[0].clone()).contains("\"fields.request_id\":\"42\",\"message\":\"Hello World! request_id=42\",\"target\":\"layer::fields\",\"usr.id\":\"Event\"}");
+}
+
+#[test]
+fn fields_can_not_be_moved_to_the_keys_of_the_layer() {
+    let (sink, _guard) = setup_fields_subscriber(|layer| {
+        layer.with_field_rules(
+            FieldRules::default()
+                .with_moved_field("user", "level")
+                .with_moved_field("request", "dd.trace_id"),
+        )
+    });
+
+    log_with_colliding_fields();
+
+    let events = sink.events();
+    assert_that(&events).size().is(1);
+
+    let log: Value = serde_json::from_str(&events[0]).unwrap();
+    assert_that(log["level"].clone()).is(Value::from("INFO"));
+    assert_that(log["fields.level"].clone()).is(Value::from("Event"));
+    assert_that(log["fields.dd.trace_id"].clone()).is(Value::from("42"));
+    assert_that(log.get("dd.trace_id").is_none()).is(true);
+}
+
+#[test]
+fn fields_can_not_be_moved_to_the_attributes_of_the_layer() {
+    let (sink, _guard) = setup_fields_subscriber(|layer| {
+        layer
+            .with_span_stack(SpanStack::default().with_current_span())
+            .with_span_summaries(SpanSummaries::default())
+            .with_field_rules(
+                FieldRules::default()
+                    .with_moved_field("user", "span")
+                    .with_moved_field("request", "duration"),
+            )
+    });
+
+    info_span!("request", user = "Jack", request = 42).in_scope(|| info!("Hello World!"));
+
+    let events = sink.events();
+    assert_that(&events).size().is(2);
+
+    let event: Value = serde_json::from_str(&events[0]).unwrap();
+    assert_that(event["span"]["name"].clone()).is(Value::from("request"));
+    assert_that(event["fields.span"].clone()).is(Value::from("Jack"));
+    // nothing else writes a duration on events
+    assert_that(event["duration"].clone()).is(Value::from("42"));
+
+    let summary: Value = serde_json::from_str(&events[1]).unwrap();
+    assert_that(summary["duration"].is_u64()).is(true);
+    assert_that(summary["fields.duration"].clone()).is(Value::from("42"));
+}
+
+fn log_with_colliding_fields() {
+    info_span!("outer", user = "Outer", request = 42).in_scope(|| {
+        info_span!("inner", user = "Inner").in_scope(|| info!(user = "Event", "Hello World!"));
+    });
+}
+
+fn setup_fields_subscriber(
+    configure: impl FnOnce(
+        DatadogFormattingLayer<ObservableSink>,
+    ) -> DatadogFormattingLayer<ObservableSink>,
+) -> (ObservableSink, DefaultGuard) {
+    let sink = ObservableSink::default();
+
+    let subscriber = tracing_subscriber::registry()
+        .with(configure(DatadogFormattingLayer::with_sink(sink.clone())));
+
+    let guard = tracing::subscriber::set_default(subscriber);
+
+    (sink, guard)
+}
+
+#[instrument(fields(access_token = "xyz"))]
+fn log_with_secrets() {
+    login("Jack", "hunter2");
+}
+
+#[instrument(skip(user))]
+fn login(user: &str, password: &str) {
+    info!(Authorization = "Bearer abc", user, "Logged in");
+}
@@ -8,8 +8,14 @@
 use datadog_formatting_layer::EventSink;
 use std::sync::{Arc, Mutex};
 
+mod fields;
+mod native;
+#[cfg(feature = "otel-0_31")]
 mod otel;
+#[cfg(feature = "otel-0_30")]
+mod otel_0_30;
 mod simple;
+mod summary;
 
 #[derive(Debug, Clone, Default)]
 struct ObservableSink {
@@ -0,0 +1,233 @@
+use crate::ObservableSink;
+use datadog_formatting_layer::{
+    propagation, CorrelationIds, DatadogFormattingLayer, HexIdKeys, NativeCorrelation,
+    NoCorrelation,
+};
+use serde_json::Value;
+use smoothy::prelude::*;
+use std::collections::HashMap;
+use tracing::{dispatcher::DefaultGuard, info, info_span};
+use tracing_subscriber::{filter::LevelFilter, prelude::*};
+
+#[test]
+fn child_spans_inherit_the_trace_id() {
+    let (sink, _guard) = setup_native_subscriber(NativeCorrelation::new());
+
+    let root = info_span!("root");
+    let child = info_span!(parent: &root, "child");
+    root.in_scope(|| info!("In root"));
+    child.in_scope(|| info!("In child"));
+
+    let events = sink.events();
+    assert_that(&events).size().is(2);
+
+    let (root_trace_id, root_span_id) = ids(&events[0]);
+    let (child_trace_id, child_span_id) = ids(&events[1]);
+
+    assert_that(root_trace_id).is_some();
+    assert_that(root_span_id).is_some();
+    assert_that(child_trace_id).is(root_trace_id);
+    assert_that(child_span_id == root_span_id).is(false);
+}
+
+#[test]
+fn root_spans_start_new_traces() {
+    let (sink, _guard) = setup_native_subscriber(NativeCorrelation::new());
+
+    info_span!("first").in_scope(|| info!("In first"));
+    info_span!("second").in_scope(|| info!("In second"));
+
+    let events = sink.events();
+    assert_that(&events).size().is(2);
+
+    assert_that(ids(&events[0]).0 == ids(&events[1]).0).is(false);
+}
+
+#[test]
+fn events_outside_of_spans_are_not_correlated() {
+    let (sink, _guard) = setup_native_subscriber(NativeCorrelation::new());
+
+    info!("Outside");
+
+    let events = sink.events();
+    assert_that(&events).size().is(1);
+
+    assert_that(ids(&events[0])).is((None, None));
+}
+
+#[test]
+fn root_spans_continue_the_seeded_trace() {
+    let (sink, _guard) = setup_native_subscriber(NativeCorrelation::new());
+
+    let span = {
+        let _seed = NativeCorrelation::seed(CorrelationIds::new(42, 7));
+        info!("Seeded");
+        info_span!("request")
+    };
+    span.in_scope(|| info!("In request"));
+    info_span!("unrelated").in_scope(|| info!("In unrelated"));
+
+    let events = sink.events();
+    assert_that(&events).size().is(3);
+
+    assert_that(ids(&events[0])).is((Some(42), Some(7)));
+
+    let (trace_id, span_id) = ids(&events[1]);
+    assert_that(trace_id).is(Some(42));
+    assert_that(span_id == Some(7)).is(false);
+
+    assert_that(ids(&events[2]).0 == Some(42)).is(false);
+}
+
+#[test]
+fn extracted_parents_are_continued() {
+    let (sink, _guard) = setup_native_subscriber(NativeCorrelation::new());
+
+    let incoming = HashMap::from([
+        ("x-datadog-trace-id".to_string(), "42".to_string()),
+        ("x-datadog-parent-id".to_string(), "7".to_string()),
+    ]);
+
+    let span = info_span!("request");
+    let continued = NativeCorrelation::set_parent(&span, propagation::extract(&incoming).unwrap());
+    assert_that(continued).is(true);
+
+    let mut outgoing = HashMap::new();
+    span.in_scope(|| {
+        info!("In request");
+        info_span!("call").in_scope(|| {
+            propagation::inject(&NativeCorrelation::current().unwrap(), &mut outgoing);
+        });
+    });
+
+    let events = sink.events();
+    assert_that(&events).size().is(1);
+
+    let (trace_id, span_id) = ids(&events[0]);
+    assert_that(trace_id).is(Some(42));
+    assert_that(span_id == Some(7)).is(false);
+
+    assert_that(outgoing["x-datadog-trace-id"].clone()).is("42".to_string());
+    assert_that(outgoing["x-datadog-parent-id"] == span_id.unwrap().to_string()).is(false);
+}
+
+#[test]
+fn unsampled_seeds_are_not_correlated_if_disabled() {
+    let sink = ObservableSink::default();
+    let subscriber = tracing_subscriber::registry().with(
+        DatadogFormattingLayer::with_sink(sink.clone())
+            .with_correlation(NativeCorrelation::new())
+            .with_unsampled_trace_ids(false),
+    );
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let span = {
+        let _seed = NativeCorrelation::seed(CorrelationIds::new(42, 7).with_sampled(false));
+        info_span!("request")
+    };
+    span.in_scope(|| info!("In request"));
+
+    let events = sink.events();
+    assert_that(&events).size().is(1);
+
+    assert_that(ids(&events[0])).is((None, None));
+}
+
+#[test]
+fn current_ids_can_be_read_for_propagation() {
+    let (sink, _guard) = setup_native_subscriber(NativeCorrelation::new());
+
+    let current = info_span!("request").in_scope(|| {
+        info!("In request");
+        NativeCorrelation::current()
+    });
+
+    let events = sink.events();
+    assert_that(&events).size().is(1);
+
+    let current_ids = current.unwrap();
+    assert_that(ids(&events[0])).is((
+        Some(u64::try_from(current_ids.trace_id()).unwrap()),
+        Some(current_ids.span_id()),
+    ));
+    assert_that(NativeCorrelation::current()).is_none();
+}
+
+#[test]
+fn trace_ids_can_be_128_bit() {
+    let sink = ObservableSink::default();
+    let subscriber = tracing_subscriber::registry().with(
+        DatadogFormattingLayer::with_sink(sink.clone())
+            .with_correlation(NativeCorrelation::new().with_128_bit_trace_ids())
+            .with_hex_ids(HexIdKeys::default()),
+    );
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    info_span!("request").in_scope(|| info!("In request"));
+
+    let events = sink.events();
+    assert_that(&events).size().is(1);
+
+    let log: Value = serde_json::from_str(&events[0]).unwrap();
+    let hex_trace_id = log["otel.trace_id"].as_str().unwrap();
+    let (upper, lower) = hex_trace_id.split_at(16);
+
+    // unix seconds followed by 32 zero bits
+    assert_that(upper.ends_with("00000000")).is(true);
+    assert_that(upper.starts_with("00000000")).is(false);
+    assert_that(u64::from_str_radix(lower, 16).ok()).is(ids(&events[0]).0);
+}
+
+fn ids(event: &str) -> (Option<u64>, Option<u64>) {
+    let log: Value = serde_json::from_str(event).unwrap();
+
+    (log["dd.trace_id"].as_u64(), log["dd.span_id"].as_u64())
+}
+
+#[test]
+fn parents_are_set_in_layered_subscribers() {
+    let sink = ObservableSink::default();
+    let subscriber = tracing_subscriber::registry()
+        .with(LevelFilter::INFO)
+        .with(
+            DatadogFormattingLayer::with_sink(sink.clone())
+                .with_correlation(NativeCorrelation::new()),
+        )
+        .with(tracing_subscriber::fmt::layer());
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let span = info_span!("request");
+    let continued = NativeCorrelation::set_parent(&span, CorrelationIds::new(42, 7));
+    span.in_scope(|| info!("In request"));
+
+    assert_that(continued).is(true);
+    assert_that(ids(&sink.events()[0]).0).is(Some(42));
+}
+
+#[test]
+fn parents_are_not_set_without_native_correlation() {
+    let subscriber = tracing_subscriber::registry().with(
+        DatadogFormattingLayer::with_sink(ObservableSink::default())
+            .with_correlation(NoCorrelation::new()),
+    );
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let span = info_span!("request");
+
+    assert_that(NativeCorrelation::set_parent(
+        &span,
+        CorrelationIds::new(42, 7),
+    ))
+    .is(false);
+}
+
+fn setup_native_subscriber(correlation: NativeCorrelation) -> (ObservableSink, DefaultGuard) {
+    let sink = ObservableSink::default();
+
+    let subscriber = tracing_subscriber::registry()
+        .with(DatadogFormattingLayer::with_sink(sink.clone()).with_correlation(correlation));
+
+    let guard = tracing::subscriber::set_default(subscriber);
+
+    (sink, guard)
+}
@@ -1,11 +1,29 @@
 use crate::ObservableSink;
-use datadog_formatting_layer::DatadogFormattingLayer;
-use opentelemetry::{global, trace::TracerProvider};
+use datadog_formatting_layer::{
+    BaggageAttributes, DatadogFormattingLayer, HexIdKeys, OtelErrorStatus, OtelSpanAttributes,
+    SpanSummaries,
+};
+use opentelemetry::{
+    baggage::BaggageExt,
+    global,
+    trace::{
+        noop::NoopTracer, SpanContext, SpanId, Status, TraceContextExt, TraceFlags, TraceId,
+        TraceState, Tracer, TracerProvider,
+    },
+    Context as OtelContext, KeyValue,
+};
 use opentelemetry_datadog::ApiVersion;
-use opentelemetry_sdk::trace::Config;
+use opentelemetry_sdk::{
+    error::OTelSdkResult,
+    trace::{Config, Sampler, SdkTracerProvider, SpanData, SpanExporter},
+};
 use serde_json::Value;
 use smoothy::prelude::*;
-use tracing::{debug, dispatcher::DefaultGuard, error, info, instrument, span, warn, Level};
+use std::{
+    future::{ready, Future},
+    sync::{Arc, Mutex},
+};
+use tracing::{debug, dispatcher::DefaultGuard, error, field, info, instrument, span, warn, Level};
 use tracing_subscriber::{filter::Targets, prelude::*};
 
 #[test]
@@ -66,6 +84,348 @@ fn events_in_nested_spans_have_correct_ids() {
     assert_that(events[2].span_id()).is(events[0].span_id());
 }
 
+#[test]
+fn events_with_explicit_parent_use_ids_of_that_parent() {
+    let (sink, _guard) = setup_otel_subscriber();
+
+    let parent = span!(Level::INFO, "parent", hello = "world");
+
+    span!(Level::INFO, "current span").in_scope(|| {
+        debug!("This has the ids of the current span");
+        info!(parent: &parent, "This has the ids of the explicit parent");
+    });
+
+    parent.in_scope(|| debug!("This has the ids of the parent"));
+
+    let events = sink.events();
+    assert_that(&events).size().is(3);
+
+    assert_that(events[0].trace_id()).is_valid();
+    assert_that(events[0].span_id()).is_valid();
+    // the explicit parent is used for both fields and ids
+    assert_that(events[1].clone()).contains("\"fields.hello\":\"world\"");
+    assert_that(events[1].trace_id()).is_valid();
+    assert_that(events[1].trace_id()).is_not(events[0].trace_id());
+    assert_that(events[1].trace_id()).is(events[2].trace_id());
+    assert_that(events[1].span_id()).is(events[2].span_id());
+}
+
+#[test]
+fn events_without_parent_have_no_datadog_ids() {
+    let (sink, _guard) = setup_otel_subscriber();
+
+    span!(Level::INFO, "span", hello = "world").in_scope(|| {
+        info!(parent: None, "This has neither fields nor ids of the current span");
+    });
+
+    let events = sink.events();
+    assert_that(&events).size().is(1);
+
+    assert_that(events[0].contains("fields.hello")).is(false);
+    assert_that(events[0].trace_id()).is_none();
+    assert_that(events[0].span_id()).is_none();
+}
+
+#[test]
+fn events_outside_spans_use_the_active_otel_context() {
+    let (sink, _guard) = setup_otel_subscriber();
+
+    let _otel_guard = remote_context().attach();
+
+    info!("This has the ids of the remote context");
+
+    let events = sink.events();
+    assert_that(&events).size().is(1);
+
+    assert_that(events[0].trace_id()).is(Some(REMOTE_TRACE_ID));
+    assert_that(events[0].span_id()).is(Some(REMOTE_SPAN_ID));
+}
+
+#[test]
+fn tracing_spans_take_precedence_over_the_active_otel_context() {
+    let (sink, _guard) = setup_otel_subscriber();
+
+    let _otel_guard = remote_context().attach();
+
+    span!(Level::INFO, "span").in_scope(|| info!("This has the ids of the span"));
+    info!(parent: None, "This has no ids");
+
+    let events = sink.events();
+    assert_that(&events).size().is(2);
+
+    // the span continues the remote trace
+    assert_that(events[0].trace_id()).is(Some(REMOTE_TRACE_ID));
+    assert_that(events[0].span_id()).is_valid();
+    assert_that(events[0].span_id()).is_not(Some(REMOTE_SPAN_ID));
+    assert_that(events[1].trace_id()).is_none();
+    assert_that(events[1].span_id()).is_none();
+}
+
+#[test]
+fn allow_listed_baggage_entries_are_written_as_attributes() {
+    let (sink, _guard) = setup_otel_subscriber_with(|layer| {
+        layer.with_baggage(BaggageAttributes::new(["tenant.id", "cohort"]))
+    });
+
+    let _otel_guard = OtelContext::current_with_baggage([
+        KeyValue::new("tenant.id", "acme"),
+        KeyValue::new("secret", "do not log me"),
+    ])
+    .attach();
+
+    info!("Outside of a span");
+    span!(Level::INFO, "span").in_scope(|| info!("The span inherits the baggage"));
+
+    let events = sink.events();
+    assert_that(&events).size().is(2);
+
+    for event in events {
+        assert_that(event.attribute("baggage.tenant.id")).is(Some("acme".to_string()));
+        assert_that(event.attribute("baggage.cohort")).is_none();
+        assert_that(event.attribute("baggage.secret")).is_none();
+    }
+}
+
+#[test]
+fn baggage_prefix_is_configurable() {
+    let (sink, _guard) = setup_otel_subscriber_with(|layer| {
+        layer.with_baggage(BaggageAttributes::new(["tenant.id"]).with_prefix("ctx."))
+    });
+
+    let _otel_guard =
+        OtelContext::current_with_baggage([KeyValue::new("tenant.id", "acme")]).attach();
+
+    info!("Hello World!");
+
+    let events = sink.events();
+    assert_that(&events).size().is(1);
+
+    assert_that(events[0].attribute("ctx.tenant.id")).is(Some("acme".to_string()));
+    assert_that(events[0].attribute("baggage.tenant.id")).is_none();
+}
+
+#[test]
+fn baggage_does_not_overwrite_the_core_keys() {
+    let (sink, _guard) = setup_otel_subscriber_with(|layer| {
+        layer
+            .with_baggage(BaggageAttributes::new(["message", "level", "tenant.id"]).with_prefix(""))
+    });
+
+    let _otel_guard = OtelContext::current_with_baggage([
+        KeyValue::new("message", "from baggage"),
+        KeyValue::new("level", "gold"),
+        KeyValue::new("tenant.id", "acme"),
+    ])
+    .attach();
+
+    info!("Hello World!");
+
+    let events = sink.events();
+    assert_that(&events).size().is(1);
+
+    assert_that(events[0].attribute("message")).is(Some("Hello World!".to_string()));
+    assert_that(events[0].attribute("level")).is(Some("INFO".to_string()));
+    assert_that(events[0].attribute("tenant.id")).is(Some("acme".to_string()));
+}
+
+#[test]
+fn baggage_is_not_written_by_default() {
+    let (sink, _guard) = setup_otel_subscriber();
+
+    let _otel_guard =
+        OtelContext::current_with_baggage([KeyValue::new("tenant.id", "acme")]).attach();
+
+    info!("Hello World!");
+
+    let events = sink.events();
+    assert_that(&events).size().is(1);
+
+    assert_that(events[0].attribute("baggage.tenant.id")).is_none();
+}
+
+#[test]
+fn span_attributes_are_written_under_their_attribute_names() {
+    let (sink, _guard) = setup_otel_subscriber_with(|layer| {
+        layer.with_otel_span_attributes(OtelSpanAttributes::default().with_name().with_kind())
+    });
+
+    let span = span!(
+        Level::INFO,
+        "request",
+        http.route = "/users/{id}",
+        otel.name = "GET /users/{id}",
+        otel.kind = "Server",
+        otel.status_code = "ok",
+        db.system = field::Empty,
+    );
+
+    span.in_scope(|| {
+        info!("Outer span");
+        span!(Level::INFO, "query").in_scope(|| info!("Inner span"));
+    });
+
+    span.record("db.system", "postgresql");
+    span.in_scope(|| info!("Recorded later"));
+
+    let events = sink.events();
+    assert_that(&events).size().is(3);
+
+    assert_that(events[0].attribute("http.route")).is(Some("/users/{id}".to_string()));
+    assert_that(events[0].attribute("otel.name")).is(Some("GET /users/{id}".to_string()));
+    assert_that(events[0].attribute("otel.kind")).is(Some("server".to_string()));
+    assert_that(events[0].attribute("otel.status_code")).is_none();
+    assert_that(events[0].attribute("db.system")).is_none();
+    // only the attributes of the span itself are written
+    assert_that(events[1].attribute("http.route")).is_none();
+    assert_that(events[1].attribute("otel.name")).is(Some("query".to_string()));
+    assert_that(events[1].attribute("otel.kind")).is(Some("internal".to_string()));
+    // recorded values are included as well
+    assert_that(events[2].attribute("db.system")).is(Some("postgresql".to_string()));
+}
+
+#[test]
+fn span_attributes_do_not_overwrite_the_core_keys() {
+    let (sink, _guard) = setup_otel_subscriber_with(|layer| {
+        layer.with_otel_span_attributes(OtelSpanAttributes::default())
+    });
+
+    span!(
+        Level::INFO,
+        "request",
+        level = "gold",
+        message = "from span",
+        timestamp = 1,
+        status = "active",
+        host = "x",
+        service = "other",
+        ddtags = "team:x",
+        http.route = "/users",
+    )
+    .in_scope(|| info!("Hello World!"));
+
+    let events = sink.events();
+    assert_that(&events).size().is(1);
+
+    let log: Value = serde_json::from_str(&events[0]).unwrap();
+    assert_that(log["level"].clone()).is(Value::from("INFO"));
+    assert_that(log["message"].as_str().unwrap()).starts_with("Hello World!");
+    assert_that(log["timestamp"].as_str().unwrap()).is_not("1");
+    assert_that(log.get("status")).is_none();
+    assert_that(log.get("host")).is_none();
+    assert_that(log.get("service")).is_none();
+    assert_that(log.get("ddtags")).is_none();
+    assert_that(log["http.route"].clone()).is(Value::from("/users"));
+}
+
+#[test]
+fn span_attributes_keep_numbers_and_booleans() {
+    let (sink, _guard) = setup_otel_subscriber_with(|layer| {
+        layer.with_otel_span_attributes(OtelSpanAttributes::default())
+    });
+
+    span!(
+        Level::INFO,
+        "request",
+        http.status_code = 200,
+        http.retry = true,
+        http.ratio = 0.5,
+        http.method = "200",
+    )
+    .in_scope(|| info!("Hello World!"));
+
+    let events = sink.events();
+    assert_that(&events).size().is(1);
+
+    let log: Value = serde_json::from_str(&events[0]).unwrap();
+    assert_that(log["http.status_code"].clone()).is(Value::from(200));
+    assert_that(log["http.retry"].clone()).is(Value::from(true));
+    assert_that(log["http.ratio"].clone()).is(Value::from(0.5));
+    assert_that(log["http.method"].clone()).is(Value::from("200"));
+}
+
+#[test]
+fn span_name_and_kind_are_optional() {
+    let (sink, _guard) = setup_otel_subscriber_with(|layer| {
+        layer.with_otel_span_attributes(OtelSpanAttributes::default())
+    });
+
+    span!(Level::INFO, "request", http.route = "/users").in_scope(|| info!("Hello World!"));
+
+    let events = sink.events();
+    assert_that(&events).size().is(1);
+
+    assert_that(events[0].attribute("http.route")).is(Some("/users".to_string()));
+    assert_that(events[0].attribute("otel.name")).is_none();
+    assert_that(events[0].attribute("otel.kind")).is_none();
+}
+
+#[test]
+fn error_events_mark_the_otel_span_as_errored() {
+    let exporter = CollectingExporter::default();
+    let tracer = exporter.provider().tracer("my-service");
+    let (_sink, _guard) = setup_subscriber_with_tracer(tracer, |layer| {
+        layer.with_otel_error_status(OtelErrorStatus::default())
+    });
+
+    span!(Level::INFO, "failing").in_scope(|| error!(error.kind = "Timeout", "Request failed"));
+    span!(Level::INFO, "failing with error field")
+        .in_scope(|| error!(error = "connection refused", "Request failed"));
+    span!(Level::INFO, "warning").in_scope(|| warn!("Request was slow"));
+
+    let spans = exporter.spans();
+    assert_that(&spans).size().is(3);
+
+    assert_that(spans[0].status.clone()).is(Status::error("Request failed"));
+    assert_that(spans[0].attribute("error.message")).is(Some("Request failed".to_string()));
+    assert_that(spans[0].attribute("error.kind")).is(Some("Timeout".to_string()));
+
+    assert_that(spans[1].status.clone()).is(Status::error("connection refused"));
+    assert_that(spans[1].attribute("error.message")).is(Some("connection refused".to_string()));
+    assert_that(spans[1].attribute("error.kind")).is_none();
+
+    assert_that(spans[2].status.clone()).is(Status::Unset);
+    assert_that(spans[2].attribute("error.message")).is_none();
+}
+
+#[test]
+fn error_events_can_be_recorded_as_span_events() {
+    let exporter = CollectingExporter::default();
+    let tracer = exporter.provider().tracer("my-service");
+    let (_sink, _guard) = setup_subscriber_with_tracer(tracer, |layer| {
+        layer.with_otel_error_status(OtelErrorStatus::default().with_span_events())
+    });
+
+    span!(Level::INFO, "failing").in_scope(|| error!(user = "Jack", "Request failed"));
+
+    let spans = exporter.spans();
+    assert_that(&spans).size().is(1);
+
+    // one event is recorded by tracing-opentelemetry itself
+    let events = &spans[0].events.events;
+    assert_that(events.len()).is(2);
+    assert_that(events[0].name.to_string()).is("Request failed".to_string());
+    assert_that(events[0].attributes.clone()).is(vec![
+        KeyValue::new("user", "Jack"),
+        KeyValue::new("level", "ERROR"),
+    ]);
+}
+
+#[test]
+fn span_summaries_are_correlated_with_the_otel_span() {
+    let (sink, _guard) =
+        setup_otel_subscriber_with(|layer| layer.with_span_summaries(SpanSummaries::default()));
+
+    span!(Level::INFO, "span").in_scope(|| info!("Hello World!"));
+
+    let events = sink.events();
+    assert_that(&events).size().is(2);
+
+    assert_that(events[1].clone()).contains("\"message\":\"span closed\"");
+    assert_that(events[0].trace_id()).is_valid();
+    assert_that(events[1].trace_id()).is(events[0].trace_id());
+    assert_that(events[1].span_id()).is(events[0].span_id());
+}
+
 #[test]
 fn events_created_by_instrument_macro_are_correctly_printed() {
     #[allow(clippy::let_underscore_untyped, clippy::let_underscore_must_use)]
@@ -91,9 +451,190 @@ fn events_created_by_instrument_macro_are_correctly_printed() {
     assert_that(events).third().contains("\"level\":\"INFO\",\"fields.args\":\"Span\",\"fields.return\":\"()\",\"message\":\" args=Span return=()\",\"target\":\"layer::otel\"");
 }
 
-fn setup_otel_subscriber() -> (ObservableSink, DefaultGuard) {
+#[test]
+fn hex_ids_are_emitted_next_to_datadog_ids() {
+    let (sink, _guard) =
+        setup_otel_subscriber_with(|layer| layer.with_hex_ids(HexIdKeys::default()));
+
+    info!("No trace or span");
+
+    span!(Level::INFO, "span").in_scope(|| debug!("This has a trace and a span"));
+
+    let events = sink.events();
+    assert_that(&events).size().is(2);
+
+    assert_that(events[0].attribute("otel.trace_id")).is_none();
+    assert_that(events[0].attribute("otel.span_id")).is_none();
+
+    let hex_trace_id = events[1].attribute("otel.trace_id").unwrap();
+    let hex_span_id = events[1].attribute("otel.span_id").unwrap();
+    assert_that(hex_trace_id.len()).is(32);
+    assert_that(hex_span_id.len()).is(16);
+    // the datadog trace id are the lower 64 bits of the otel trace id
+    let lower_trace_id = hex_trace_id.get(16..).unwrap();
+    assert_that(u64::from_str_radix(lower_trace_id, 16).ok()).is(events[1].trace_id());
+    assert_that(u64::from_str_radix(&hex_span_id, 16).ok()).is(events[1].span_id());
+}
+
+#[test]
+fn hex_id_keys_are_configurable() {
+    let (sink, _guard) = setup_otel_subscriber_with(|layer| layer.with_hex_ids(HexIdKeys::plain()));
+
+    span!(Level::INFO, "span").in_scope(|| debug!("This has a trace and a span"));
+
+    let events = sink.events();
+    assert_that(&events).size().is(1);
+
+    assert_that(events[0].attribute("trace_id")).is_some();
+    assert_that(events[0].attribute("span_id")).is_some();
+    assert_that(events[0].attribute("otel.trace_id")).is_none();
+}
+
+#[test]
+fn spans_without_valid_ids_have_no_datadog_ids() {
+    let (sink, _guard) = setup_subscriber_with_tracer(NoopTracer::new(), |layer| layer);
+
+    span!(Level::INFO, "span").in_scope(|| debug!("This span has no valid trace id"));
+
+    let events = sink.events();
+
+    assert_that(&events).size().is(1);
+    assert_that(events[0].trace_id()).is_none();
+    assert_that(events[0].span_id()).is_none();
+}
+
+#[test]
+fn spans_without_valid_ids_fall_back_to_the_active_otel_context() {
     let sink = ObservableSink::default();
+    // entering the span must not replace the active context with its invalid one
+    let subscriber = tracing_subscriber::registry()
+        .with(DatadogFormattingLayer::with_sink(sink.clone()))
+        .with(
+            tracing_opentelemetry::layer()
+                .with_tracer(NoopTracer::new())
+                .with_context_activation(false),
+        );
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let span = span!(Level::INFO, "span");
+    let _otel_guard = remote_context().attach();
 
+    span.in_scope(|| debug!("This has the ids of the remote context"));
+
+    let events = sink.events();
+
+    assert_that(&events).size().is(1);
+    assert_that(events[0].trace_id()).is(Some(REMOTE_TRACE_ID));
+    assert_that(events[0].span_id()).is(Some(REMOTE_SPAN_ID));
+}
+
+#[test]
+fn unsampled_spans_have_datadog_ids_by_default() {
+    let tracer = unsampled_provider().tracer("my-service");
+    let (sink, _guard) = setup_subscriber_with_tracer(tracer, |layer| layer);
+
+    span!(Level::INFO, "span").in_scope(|| debug!("This span was dropped by the sampler"));
+
+    let events = sink.events();
+
+    assert_that(&events).size().is(1);
+    assert_that(events[0].trace_id()).is_valid();
+    assert_that(events[0].span_id()).is_valid();
+}
+
+#[test]
+fn unsampled_spans_can_be_excluded_from_correlation() {
+    let tracer = unsampled_provider().tracer("my-service");
+    let (sink, _guard) =
+        setup_subscriber_with_tracer(tracer, |layer| layer.with_unsampled_trace_ids(false));
+
+    span!(Level::INFO, "span").in_scope(|| debug!("This span was dropped by the sampler"));
+
+    let events = sink.events();
+
+    assert_that(&events).size().is(1);
+    assert_that(events[0].trace_id()).is_none();
+    assert_that(events[0].span_id()).is_none();
+}
+
+#[test]
+fn sampled_spans_are_correlated_when_unsampled_are_excluded() {
+    let (sink, _guard) = setup_otel_subscriber_with(|layer| layer.with_unsampled_trace_ids(false));
+
+    span!(Level::INFO, "span").in_scope(|| debug!("This span was sampled"));
+
+    let events = sink.events();
+
+    assert_that(&events).size().is(1);
+    assert_that(events[0].trace_id()).is_valid();
+    assert_that(events[0].span_id()).is_valid();
+}
+
+#[derive(Debug, Clone, Default)]
+struct CollectingExporter {
+    spans: Arc<Mutex<Vec<SpanData>>>,
+}
+
+impl CollectingExporter {
+    fn provider(&self) -> SdkTracerProvider {
+        SdkTracerProvider::builder()
+            .with_simple_exporter(self.clone())
+            .build()
+    }
+
+    fn spans(&self) -> Vec<SpanData> {
+        self.spans.lock().unwrap().clone()
+    }
+}
+
+impl SpanExporter for CollectingExporter {
+    fn export(&self, batch: Vec<SpanData>) -> impl Future<Output = OTelSdkResult> + Send {
+        self.spans.lock().unwrap().extend(batch);
+        ready(Ok(()))
+    }
+}
+
+trait SpanDataExt {
+    fn attribute(&self, key: &str) -> Option<String>;
+}
+
+impl SpanDataExt for SpanData {
+    fn attribute(&self, key: &str) -> Option<String> {
+        self.attributes
+            .iter()
+            .find(|attribute| attribute.key.as_str() == key)
+            .map(|attribute| attribute.value.to_string())
+    }
+}
+
+const REMOTE_TRACE_ID: u64 = 42;
+const REMOTE_SPAN_ID: u64 = 7;
+
+fn remote_context() -> OtelContext {
+    OtelContext::new().with_remote_span_context(SpanContext::new(
+        TraceId::from(u128::from(REMOTE_TRACE_ID)),
+        SpanId::from(REMOTE_SPAN_ID),
+        TraceFlags::SAMPLED,
+        true,
+        TraceState::default(),
+    ))
+}
+
+fn unsampled_provider() -> SdkTracerProvider {
+    SdkTracerProvider::builder()
+        .with_sampler(Sampler::AlwaysOff)
+        .build()
+}
+
+fn setup_otel_subscriber() -> (ObservableSink, DefaultGuard) {
+    setup_otel_subscriber_with(|layer| layer)
+}
+
+fn setup_otel_subscriber_with(
+    configure: impl FnOnce(
+        DatadogFormattingLayer<ObservableSink>,
+    ) -> DatadogFormattingLayer<ObservableSink>,
+) -> (ObservableSink, DefaultGuard) {
     let provider = opentelemetry_datadog::new_pipeline()
         .with_service_name("my-service")
         .with_trace_config(Config::default())
@@ -107,9 +648,24 @@ fn setup_otel_subscriber() -> (ObservableSink, DefaultGuard) {
 
     global::set_tracer_provider(provider);
 
+    setup_subscriber_with_tracer(tracer, configure)
+}
+
+fn setup_subscriber_with_tracer<T>(
+    tracer: T,
+    configure: impl FnOnce(
+        DatadogFormattingLayer<ObservableSink>,
+    ) -> DatadogFormattingLayer<ObservableSink>,
+) -> (ObservableSink, DefaultGuard)
+where
+    T: Tracer + Send + Sync + 'static,
+    T::Span: Send + Sync,
+{
+    let sink = ObservableSink::default();
+
     let subscriber = tracing_subscriber::registry()
         .with(tracing_subscriber::fmt::layer())
-        .with(DatadogFormattingLayer::with_sink(sink.clone()))
+        .with(configure(DatadogFormattingLayer::with_sink(sink.clone())))
         .with(tracing_opentelemetry::layer().with_tracer(tracer))
         .with(
             Targets::new()
@@ -126,6 +682,7 @@ fn setup_otel_subscriber() -> (ObservableSink, DefaultGuard) {
 pub trait LogMessageExt {
     fn span_id(&self) -> Option<u64>;
     fn trace_id(&self) -> Option<u64>;
+    fn attribute(&self, key: &str) -> Option<String>;
 }
 
 impl LogMessageExt for String {
@@ -140,6 +697,11 @@ impl LogMessageExt for String {
         log.get("dd.trace_id")
             .map(|span_id| span_id.as_u64().unwrap())
     }
+
+    fn attribute(&self, key: &str) -> Option<String> {
+        let log: Value = serde_json::from_str(self).unwrap();
+        log.get(key).map(|id| id.as_str().unwrap().to_string())
+    }
 }
 
 pub trait SmoothyExt {
@@ -0,0 +1,302 @@
+use crate::ObservableSink;
+use datadog_formatting_layer::{
+    DatadogFormattingLayer, FieldRedaction, OtelErrorStatus, OtelSpanAttributes, ValueScrubbing,
+};
+use opentelemetry_0_30::{
+    trace::{SpanContext, SpanId, Status, TraceContextExt, TraceFlags, TraceId, TraceState},
+    Context as OtelContext, KeyValue,
+};
+use opentelemetry_sdk_0_30::{
+    error::OTelSdkResult,
+    trace::{Sampler, SdkTracerProvider, SpanData, SpanExporter},
+};
+use serde_json::Value;
+use smoothy::prelude::*;
+use std::{
+    future::{ready, Future},
+    sync::{Arc, Mutex},
+};
+use tracing::{debug, dispatcher::DefaultGuard, error, info, span, Level};
+use tracing_opentelemetry_0_31::{layer, OpenTelemetrySpanExt};
+use tracing_subscriber::prelude::*;
+
+#[test]
+fn events_have_the_ids_of_the_otel_span() {
+    let exporter = CollectingExporter::default();
+    let (sink, _guard) = setup_subscriber(&exporter.provider(Sampler::AlwaysOn), |layer| layer);
+
+    span!(Level::INFO, "span").in_scope(|| info!("Hello World!"));
+
+    let events = sink.events();
+    let spans = exporter.spans();
+    assert_that(&events).size().is(1);
+    assert_that(&spans).size().is(1);
+
+    let span_context = &spans[0].span_context;
+    let trace_id = u128::from_be_bytes(span_context.trace_id().to_bytes()).to_be_bytes();
+    let (_, lower_trace_id) = trace_id.split_at(8);
+
+    assert_that(ids(&events[0])).is((
+        Some(u64::from_be_bytes(lower_trace_id.try_into().unwrap())),
+        Some(u64::from_be_bytes(span_context.span_id().to_bytes())),
+    ));
+}
+
+#[test]
+fn spans_continue_the_trace_of_the_active_otel_context() {
+    let exporter = CollectingExporter::default();
+    let (sink, _guard) = setup_subscriber(&exporter.provider(Sampler::AlwaysOn), |layer| layer);
+
+    let _otel_guard = remote_context(TraceFlags::SAMPLED).attach();
+
+    span!(Level::INFO, "span").in_scope(|| info!("This continues the remote trace"));
+
+    let events = sink.events();
+    assert_that(&events).size().is(1);
+
+    let (trace_id, span_id) = ids(&events[0]);
+    assert_that(trace_id).is(Some(REMOTE_TRACE_ID));
+    assert_that(span_id)
+        .is_some()
+        .and_value()
+        .is_not(REMOTE_SPAN_ID);
+}
+
+#[test]
+fn spans_use_the_sampling_decision_of_the_parent_until_they_are_sampled() {
+    let exporter = CollectingExporter::default();
+    let (sink, _guard) = setup_subscriber(&exporter.provider(Sampler::AlwaysOn), |layer| {
+        layer.with_unsampled_trace_ids(false)
+    });
+
+    let unsampled_guard = remote_context(TraceFlags::default()).attach();
+    span!(Level::INFO, "unsampled").in_scope(|| info!("The parent was not sampled"));
+    drop(unsampled_guard);
+
+    let _sampled_guard = remote_context(TraceFlags::SAMPLED).attach();
+    span!(Level::INFO, "sampled").in_scope(|| info!("The parent was sampled"));
+
+    let events = sink.events();
+    assert_that(&events).size().is(2);
+
+    assert_that(ids(&events[0])).is((None, None));
+    assert_that(ids(&events[1]).0).is(Some(REMOTE_TRACE_ID));
+}
+
+#[test]
+fn spans_use_the_sampling_decision_of_the_sampler_once_it_ran() {
+    let exporter = CollectingExporter::default();
+    let (sink, _guard) = setup_subscriber(&exporter.provider(Sampler::AlwaysOff), |layer| {
+        layer.with_unsampled_trace_ids(false)
+    });
+
+    // creating the child samples the parent
+    span!(Level::INFO, "parent").in_scope(|| {
+        span!(Level::INFO, "child").in_scope(|| debug!("This trace was dropped by the sampler"));
+    });
+
+    let events = sink.events();
+    assert_that(&events).size().is(1);
+
+    assert_that(ids(&events[0])).is((None, None));
+}
+
+#[test]
+fn root_spans_are_unsampled_until_the_sampler_ran() {
+    let exporter = CollectingExporter::default();
+    let (sink, _guard) = setup_subscriber(&exporter.provider(Sampler::AlwaysOff), |layer| {
+        layer.with_unsampled_trace_ids(false)
+    });
+
+    span!(Level::INFO, "root").in_scope(|| info!("The sampler did not run yet"));
+
+    let events = sink.events();
+    assert_that(&events).size().is(1);
+
+    assert_that(ids(&events[0])).is((None, None));
+}
+
+#[test]
+fn root_spans_are_unsampled_until_the_sampler_ran_even_if_it_samples_everything() {
+    let exporter = CollectingExporter::default();
+    let (sink, _guard) = setup_subscriber(&exporter.provider(Sampler::AlwaysOn), |layer| {
+        layer.with_unsampled_trace_ids(false)
+    });
+
+    span!(Level::INFO, "root").in_scope(|| info!("The sampler did not run yet"));
+
+    let events = sink.events();
+    assert_that(&events).size().is(1);
+
+    // a known limitation: the sampler only runs once the span is closed or gets a child
+    assert_that(ids(&events[0])).is((None, None));
+    assert_that(exporter.spans()).size().is(1);
+}
+
+#[test]
+fn error_events_mark_the_otel_span_as_errored() {
+    let exporter = CollectingExporter::default();
+    let (_sink, _guard) = setup_subscriber(&exporter.provider(Sampler::AlwaysOn), |layer| {
+        layer.with_otel_error_status(OtelErrorStatus::default().with_span_events())
+    });
+
+    span!(Level::INFO, "failing").in_scope(|| error!(error.kind = "Timeout", "Request failed"));
+
+    let spans = exporter.spans();
+    assert_that(&spans).size().is(1);
+
+    assert_that(spans[0].status.clone()).is(Status::error("Request failed"));
+    assert_that(spans[0].attribute("error.message")).is(Some("Request failed".to_string()));
+    assert_that(spans[0].attribute("error.kind")).is(Some("Timeout".to_string()));
+
+    let events = &spans[0].events.events;
+    assert_that(events.iter().any(|event| {
+        event
+            .attributes
+            .contains(&KeyValue::new("error.kind", "Timeout"))
+    }))
+    .is(true);
+}
+
+#[test]
+fn attributes_of_the_otel_span_are_written() {
+    let exporter = CollectingExporter::default();
+    let (sink, _guard) = setup_subscriber(&exporter.provider(Sampler::AlwaysOn), |layer| {
+        layer.with_otel_span_attributes(OtelSpanAttributes::default().with_name().with_kind())
+    });
+
+    let span = span!(
+        Level::INFO,
+        "request",
+        http.route = "/users/{id}",
+        otel.name = "GET /users/{id}",
+        otel.kind = "server",
+    );
+    span.set_attribute("http.response.status_code", 200);
+
+    span.in_scope(|| {
+        info!("Outer span");
+        span!(Level::INFO, "query").in_scope(|| info!("Inner span"));
+    });
+
+    let events = sink.events();
+    assert_that(&events).size().is(2);
+
+    let outer: Value = serde_json::from_str(&events[0]).unwrap();
+    assert_that(outer["http.route"].clone()).is(Value::from("/users/{id}"));
+    assert_that(outer["http.response.status_code"].clone()).is(Value::from(200));
+    assert_that(outer["otel.name"].clone()).is(Value::from("GET /users/{id}"));
+    assert_that(outer["otel.kind"].clone()).is(Value::from("server"));
+
+    // only the attributes of the span itself are written
+    let inner: Value = serde_json::from_str(&events[1]).unwrap();
+    assert_that(inner.get("http.route").is_none()).is(true);
+    assert_that(inner["otel.name"].clone()).is(Value::from("query"));
+    assert_that(inner["otel.kind"].clone()).is(Value::from("internal"));
+}
+
+#[test]
+fn attributes_of_the_otel_span_are_redacted_and_scrubbed() {
+    let exporter = CollectingExporter::default();
+    let (sink, _guard) = setup_subscriber(&exporter.provider(Sampler::AlwaysOn), |layer| {
+        layer
+            .with_otel_span_attributes(OtelSpanAttributes::default())
+            .with_field_redaction(FieldRedaction::default())
+            .with_value_scrubbing(ValueScrubbing::default().with_emails())
+    });
+
+    span!(
+        Level::INFO,
+        "login",
+        password = "hunter2",
+        user = "jack@example.com",
+        attempt = 2
+    )
+    .in_scope(|| info!("Logged in"));
+
+    let events = sink.events();
+    assert_that(&events).size().is(1);
+
+    let log: Value = serde_json::from_str(&events[0]).unwrap();
+    assert_that(log["password"].clone()).is_not(Value::from("hunter2"));
+    assert_that(log["user"].as_str().unwrap().contains("jack@example.com")).is(false);
+    assert_that(log["attempt"].clone()).is(Value::from(2));
+    assert_that(events[0].contains("hunter2")).is(false);
+    assert_that(events[0].contains("jack@example.com")).is(false);
+}
+
+const REMOTE_TRACE_ID: u64 = 42;
+const REMOTE_SPAN_ID: u64 = 7;
+
+fn remote_context(trace_flags: TraceFlags) -> OtelContext {
+    OtelContext::new().with_remote_span_context(SpanContext::new(
+        TraceId::from(u128::from(REMOTE_TRACE_ID)),
+        SpanId::from(REMOTE_SPAN_ID),
+        trace_flags,
+        true,
+        TraceState::default(),
+    ))
+}
+
+fn ids(event: &str) -> (Option<u64>, Option<u64>) {
+    let log: Value = serde_json::from_str(event).unwrap();
+
+    (log["dd.trace_id"].as_u64(), log["dd.span_id"].as_u64())
+}
+
+fn setup_subscriber(
+    provider: &SdkTracerProvider,
+    configure: impl FnOnce(
+        DatadogFormattingLayer<ObservableSink>,
+    ) -> DatadogFormattingLayer<ObservableSink>,
+) -> (ObservableSink, DefaultGuard) {
+    use opentelemetry_0_30::trace::TracerProvider;
+
+    let sink = ObservableSink::default();
+
+    let subscriber = tracing_subscriber::registry()
+        .with(configure(DatadogFormattingLayer::with_sink(sink.clone())))
+        .with(layer().with_tracer(provider.tracer("my-service")));
+
+    let guard = tracing::subscriber::set_default(subscriber);
+
+    (sink, guard)
+}
+
+#[derive(Debug, Clone, Default)]
+struct CollectingExporter {
+    spans: Arc<Mutex<Vec<SpanData>>>,
+}
+
+impl CollectingExporter {
+    fn provider(&self, sampler: Sampler) -> SdkTracerProvider {
+        SdkTracerProvider::builder()
+            .with_sampler(sampler)
+            .with_simple_exporter(self.clone())
+            .build()
+    }
+
+    fn spans(&self) -> Vec<SpanData> {
+        self.spans.lock().unwrap().clone()
+    }
+}
+
+impl SpanExporter for CollectingExporter {
+    fn export(&self, batch: Vec<SpanData>) -> impl Future<Output = OTelSdkResult> + Send {
+        self.spans.lock().unwrap().extend(batch);
+        ready(Ok(()))
+    }
+}
+
+trait SpanDataExt {
+    fn attribute(&self, key: &str) -> Option<String>;
+}
+
+impl SpanDataExt for SpanData {
+    fn attribute(&self, key: &str) -> Option<String> {
+        self.attributes
+            .iter()
+            .find(|attribute| attribute.key.as_str() == key)
+            .map(|attribute| attribute.value.to_string())
+    }
+}
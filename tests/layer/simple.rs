@@ -1,7 +1,16 @@
 use crate::ObservableSink;
-use datadog_formatting_layer::DatadogFormattingLayer;
+use datadog_formatting_layer::{
+    DatadogFormattingLayer, FieldCollisions, GitMetadata, GlobalAttributes, KubernetesMetadata,
+    Nanos, ProcessMetadata, TracingSpanCorrelation,
+};
+use serde_json::Value;
 use smoothy::prelude::*;
-use tracing::{debug, dispatcher::DefaultGuard, info, instrument, Level};
+use std::{cell::Cell, env, fs, thread, time::Duration};
+use tracing::{
+    debug,
+    dispatcher::{self, DefaultGuard},
+    field, info, info_span, instrument, span, Dispatch, Level,
+};
 use tracing_subscriber::{prelude::*, FmtSubscriber};
 
 #[test]
@@ -30,6 +39,330 @@ fn fields_are_formatted_and_printed() {
     assert_that(events).first().contains("\",\"level\":\"INFO\",\"fields.user\":\"John Doe\",\"message\":\"Hello World! user=John Doe\",\"target\":\"layer::simple\"}");
 }
 
+#[test]
+fn fields_recorded_after_span_creation_are_printed() {
+    let (sink, _guard) = setup_simple_subscriber();
+
+    let span = span!(Level::INFO, "span", user = "John Doe", id = field::Empty);
+    span.record("user", "Jane Doe");
+    span.record("id", 42);
+
+    span.in_scope(|| info!("Hello World!"));
+
+    let events = sink.events();
+    assert_that(&events).size().is(1);
+
+    assert_that(events).first().contains("\"fields.id\":\"42\",\"fields.user\":\"Jane Doe\",\"message\":\"Hello World! id=42 user=Jane Doe\"");
+}
+
+#[test]
+fn tracing_span_ids_can_be_used_for_correlation() {
+    let sink = ObservableSink::default();
+    let subscriber = tracing_subscriber::registry().with(
+        DatadogFormattingLayer::with_sink(sink.clone())
+            .with_correlation(TracingSpanCorrelation::new()),
+    );
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    info!("Outside");
+    let root = span!(Level::INFO, "root");
+    let child = span!(parent: &root, Level::INFO, "child");
+    root.in_scope(|| info!("In root"));
+    child.in_scope(|| info!("In child"));
+
+    let ids: Vec<(Value, Value)> = sink
+        .events()
+        .iter()
+        .map(|event| {
+            let log: Value = serde_json::from_str(event).unwrap();
+            (log["dd.trace_id"].clone(), log["dd.span_id"].clone())
+        })
+        .collect();
+    assert_that(&ids).size().is(3);
+
+    let root_id = Value::from(root.id().unwrap().into_u64());
+    let child_id = Value::from(child.id().unwrap().into_u64());
+
+    assert_that(ids.clone())
+        .first()
+        .is((Value::Null, Value::Null));
+    assert_that(ids.clone())
+        .second()
+        .is((root_id.clone(), root_id.clone()));
+    assert_that(ids).third().is((root_id, child_id));
+}
+
+#[test]
+fn global_attributes_are_written_on_every_log() {
+    thread_local! {
+        static TENANT: Cell<Option<&'static str>> = const { Cell::new(None) };
+    }
+
+    let sink = ObservableSink::default();
+    let subscriber = tracing_subscriber::registry().with(
+        DatadogFormattingLayer::with_sink(sink.clone()).with_global_attributes(
+            GlobalAttributes::default()
+                .with_attribute("ddsource", "rust")
+                .with_tag("team", "payments")
+                .with_tag("region", "eu")
+                .with_dynamic_attribute("tenant.id", || TENANT.get().map(Into::into)),
+        ),
+    );
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    info!(ddsource = "event", "Without tenant");
+    TENANT.set(Some("acme"));
+    info!("With tenant");
+
+    let events = sink.events();
+    assert_that(&events).size().is(2);
+
+    assert_that(events[0].clone()).contains("\"fields.ddsource\":\"event\",\"message\":\"Without tenant ddsource=event\",\"target\":\"layer::simple\",\"ddsource\":\"rust\",\"ddtags\":\"team:payments,region:eu\"}");
+    assert_that(events[1].clone()).contains("\"message\":\"With tenant\",\"target\":\"layer::simple\",\"tenant.id\":\"acme\",\"ddsource\":\"rust\",\"ddtags\":\"team:payments,region:eu\"}");
+}
+
+#[test]
+fn ddtags_attributes_are_merged_with_the_tags() {
+    let sink = ObservableSink::default();
+    let subscriber = tracing_subscriber::registry().with(
+        DatadogFormattingLayer::with_sink(sink.clone()).with_global_attributes(
+            GlobalAttributes::default()
+                .with_attribute("ddtags", "env:prod")
+                .with_tag("team", "x")
+                .with_dynamic_attribute("ddtags", || Some("tenant:acme".into())),
+        ),
+    );
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    info!("Hello");
+
+    let events = sink.events();
+    assert_that(&events).size().is(1);
+
+    assert_that(events[0].clone())
+        .contains("\"target\":\"layer::simple\",\"ddtags\":\"tenant:acme,env:prod,team:x\"}");
+}
+
+#[test]
+fn process_metadata_is_written_on_every_log() {
+    let sink = ObservableSink::default();
+    let dispatch = Dispatch::new(
+        tracing_subscriber::registry().with(
+            DatadogFormattingLayer::with_sink(sink.clone()).with_process_metadata(
+                ProcessMetadata::default()
+                    .with_pid()
+                    .with_executable()
+                    .with_thread(),
+            ),
+        ),
+    );
+
+    thread::scope(|scope| {
+        thread::Builder::new()
+            .name("worker".to_string())
+            .spawn_scoped(scope, || {
+                dispatcher::with_default(&dispatch, || info!("Named thread"));
+            })
+            .unwrap();
+    });
+    thread::scope(|scope| {
+        scope.spawn(|| dispatcher::with_default(&dispatch, || info!("Unnamed thread")));
+    });
+
+    let events = sink.events();
+    assert_that(&events).size().is(2);
+
+    let named: Value = serde_json::from_str(&events[0]).unwrap();
+    assert_that(named["process.pid"].clone()).is(Value::from(std::process::id()));
+    assert_that(named["process.executable.name"].is_string()).is(true);
+    assert_that(named["logger.thread_name"].clone()).is(Value::from("worker"));
+    assert_that(named["thread.id"].is_u64()).is(true);
+
+    let unnamed: Value = serde_json::from_str(&events[1]).unwrap();
+    assert_that(unnamed.get("logger.thread_name").is_none()).is(true);
+    assert_that(unnamed["thread.id"].clone()).is_not(named["thread.id"].clone());
+}
+
+#[test]
+fn kubernetes_metadata_is_read_from_the_cgroup() {
+    let container_id = "3f1d8c2b9a7e6d5c4b3a29180f7e6d5c4b3a29180f7e6d5c4b3a29180f7e6d5c";
+    let cgroup = env::temp_dir().join(format!(
+        "datadog-formatting-layer-{}-simple-cgroup",
+        std::process::id()
+    ));
+    fs::write(
+        &cgroup,
+        format!("0::/kubepods.slice/kubepods-pod1234.slice/cri-containerd-{container_id}.scope\n"),
+    )
+    .unwrap();
+
+    // variables which are not set are omitted
+    let metadata = KubernetesMetadata::default()
+        .with_pod_name_var("TEST_KUBE_POD_NAME")
+        .with_namespace_var("TEST_KUBE_NAMESPACE")
+        .with_node_name_var("TEST_KUBE_NODE_NAME")
+        .with_container_name_var("TEST_KUBE_CONTAINER_NAME")
+        .with_cgroup_path(&cgroup);
+
+    let sink = ObservableSink::default();
+    let subscriber = tracing_subscriber::registry()
+        .with(
+            DatadogFormattingLayer::with_sink(sink.clone()).with_global_attributes(
+                GlobalAttributes::default().with_kubernetes_metadata(&metadata),
+            ),
+        )
+        .with(
+            DatadogFormattingLayer::with_sink(sink.clone()).with_global_attributes(
+                GlobalAttributes::default()
+                    .with_tag("team", "payments")
+                    .with_kubernetes_metadata(&metadata.with_tags()),
+            ),
+        );
+    let _guard = tracing::subscriber::set_default(subscriber);
+    fs::remove_file(&cgroup).unwrap();
+
+    info!("Hello");
+
+    let events = sink.events();
+    assert_that(&events).size().is(2);
+
+    assert_that(events[0].clone()).contains(format!(
+        "\"target\":\"layer::simple\",\"container_id\":\"{container_id}\"}}"
+    ));
+    assert_that(events[1].clone()).contains(format!(
+        "\"target\":\"layer::simple\",\"ddtags\":\"team:payments,container_id:{container_id}\"}}"
+    ));
+}
+
+#[test]
+fn git_metadata_is_written_as_attributes_or_tags() {
+    let metadata = GitMetadata::from_values(
+        Some("8f3a1c2d9e7b6a5f4e3d2c1b0a9f8e7d6c5b4a39\n"),
+        Some("https://github.com/example/payments-api"),
+    );
+
+    let sink = ObservableSink::default();
+    let subscriber = tracing_subscriber::registry()
+        .with(
+            DatadogFormattingLayer::with_sink(sink.clone())
+                .with_global_attributes(GlobalAttributes::default().with_git_metadata(&metadata)),
+        )
+        .with(
+            DatadogFormattingLayer::with_sink(sink.clone()).with_global_attributes(
+                GlobalAttributes::default().with_git_metadata(&metadata.with_tags()),
+            ),
+        );
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    info!("Hello");
+
+    let events = sink.events();
+    assert_that(&events).size().is(2);
+
+    assert_that(events[0].clone()).contains("\"target\":\"layer::simple\",\"git.commit.sha\":\"8f3a1c2d9e7b6a5f4e3d2c1b0a9f8e7d6c5b4a39\",\"git.repository_url\":\"https://github.com/example/payments-api\"}");
+    assert_that(events[1].clone()).contains("\"target\":\"layer::simple\",\"ddtags\":\"git.commit.sha:8f3a1c2d9e7b6a5f4e3d2c1b0a9f8e7d6c5b4a39,git.repository_url:https://github.com/example/payments-api\"}");
+}
+
+#[test]
+fn well_known_fields_can_be_written_as_standard_attributes() {
+    let sink = ObservableSink::default();
+    let subscriber = tracing_subscriber::registry()
+        .with(DatadogFormattingLayer::with_sink(sink.clone()).with_standard_attributes(true));
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    info_span!("request", http.request.method = "POST", enduser.id = "jack").in_scope(|| {
+        info!(http.response.status_code = 201, "Created");
+    });
+
+    let events = sink.events();
+    assert_that(&events).size().is(1);
+
+    assert_that(events[0].clone()).contains("\"message\":\"Created\",\"target\":\"layer::simple\",\"usr.id\":\"jack\",\"http.method\":\"POST\",\"http.status_code\":201}");
+}
+
+#[test]
+fn standard_attributes_of_spans_and_events_are_all_written() {
+    let sink = ObservableSink::default();
+    let subscriber = tracing_subscriber::registry().with(
+        DatadogFormattingLayer::with_sink(sink.clone())
+            .with_standard_attributes(true)
+            .with_field_collisions(FieldCollisions::KeepAll),
+    );
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    info_span!("request", http.status_code = 200).in_scope(|| {
+        info!(http.status_code = 500, "Failed");
+    });
+
+    let events = sink.events();
+    assert_that(&events).size().is(1);
+
+    assert_that(events[0].clone()).contains(
+        "\"message\":\"Failed\",\"target\":\"layer::simple\",\"http.status_code\":[200,500]}",
+    );
+}
+
+#[test]
+fn non_finite_numbers_are_not_written_as_standard_attributes() {
+    let sink = ObservableSink::default();
+    let subscriber = tracing_subscriber::registry()
+        .with(DatadogFormattingLayer::with_sink(sink.clone()).with_standard_attributes(true));
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    info!(duration = %"inf", "Infinite");
+    info!(duration = %"NaN", "Not a number");
+
+    let events = sink.events();
+    assert_that(&events).size().is(2);
+
+    assert_that(events[0].clone()).contains("\"fields.duration\":\"inf\"");
+    assert_that(events[1].clone()).contains("\"fields.duration\":\"NaN\"");
+    for event in &events {
+        assert_that(event.contains("\"duration\":")).is(false);
+    }
+}
+
+#[test]
+fn durations_can_be_written_as_nanoseconds() {
+    let sink = ObservableSink::default();
+    let subscriber = tracing_subscriber::registry()
+        .with(DatadogFormattingLayer::with_sink(sink.clone()).with_normalized_durations(true))
+        .with(
+            DatadogFormattingLayer::with_sink(sink.clone())
+                .with_normalized_durations(true)
+                .with_standard_attributes(true),
+        );
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    info!(
+        duration = ?Nanos(Duration::from_micros(12_300)),
+        db.duration = %Nanos(Duration::from_nanos(1_500_000_001)),
+        label = %"5s",
+        "Queried"
+    );
+
+    let events = sink.events();
+    assert_that(&events).size().is(2);
+
+    assert_that(events[0].clone()).contains("\"fields.db.duration\":1500000001,\"fields.duration\":12300000,\"fields.label\":\"5s\",\"message\":\"Queried db.duration=1.500000001s duration=12.3ms label=5s\"");
+    assert_that(events[1].clone()).contains("\"fields.db.duration\":1500000001,\"fields.label\":\"5s\",\"message\":\"Queried db.duration=1.500000001s label=5s\",\"target\":\"layer::simple\",\"duration\":12300000}");
+}
+
+#[test]
+fn only_wrapped_durations_are_written_as_nanoseconds() {
+    let sink = ObservableSink::default();
+    let subscriber = tracing_subscriber::registry()
+        .with(DatadogFormattingLayer::with_sink(sink.clone()).with_normalized_durations(true));
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    info!(label = %"5s", elapsed = ?Duration::from_secs(5), "Queried");
+
+    let events = sink.events();
+    assert_that(&events).size().is(1);
+
+    assert_that(events[0].clone()).contains("\"fields.elapsed\":\"5s\",\"fields.label\":\"5s\",\"message\":\"Queried elapsed=5s label=5s\"");
+}
+
 #[allow(clippy::redundant_clone)]
 #[test]
 fn complex_logs() {
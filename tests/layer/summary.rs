@@ -0,0 +1,144 @@
+use crate::ObservableSink;
+use datadog_formatting_layer::{
+    DatadogFormattingLayer, NativeCorrelation, SpanFieldInheritance, SpanSummaries,
+};
+use serde_json::Value;
+use smoothy::prelude::*;
+use std::{
+    thread::sleep,
+    time::{Duration, Instant},
+};
+use tracing::{dispatcher::DefaultGuard, info, info_span, warn_span};
+use tracing_subscriber::prelude::*;
+
+#[test]
+fn closed_spans_are_summarized() {
+    let (sink, _guard) = setup_summary_subscriber(SpanSummaries::default());
+
+    let start = Instant::now();
+    let span = warn_span!("request", user = "Jack");
+    span.in_scope(|| sleep(Duration::from_millis(10)));
+    sleep(Duration::from_millis(10));
+    drop(span);
+    let elapsed = u64::try_from(start.elapsed().as_nanos()).unwrap();
+
+    let events = sink.events();
+    assert_that(&events).size().is(1);
+
+    assert_that(events[0].clone()).contains("\"level\":\"WARN\",\"fields.user\":\"Jack\",\"message\":\"request closed user=Jack\",\"target\":\"layer::summary\"");
+
+    let log: Value = serde_json::from_str(&events[0]).unwrap();
+    let duration = log["duration"].as_u64().unwrap();
+    let busy = log["span.busy_ns"].as_u64().unwrap();
+    let idle = log["span.idle_ns"].as_u64().unwrap();
+
+    // bounded by the sleeps inside and outside of the span and the time measured around it
+    assert_that(log["span.name"].clone()).is(Value::from("request"));
+    assert_that(duration >= 20_000_000).is(true);
+    assert_that(duration <= elapsed).is(true);
+    assert_that(busy >= 10_000_000).is(true);
+    assert_that(busy < duration - 10_000_000).is(true);
+    assert_that(idle >= 10_000_000).is(true);
+}
+
+#[test]
+fn summaries_are_written_after_the_events_of_the_span() {
+    let (sink, _guard) = setup_summary_subscriber(SpanSummaries::default());
+
+    info_span!("outer", user = "Jack").in_scope(|| {
+        info_span!("inner").in_scope(|| info!("Hello World!"));
+    });
+
+    let events = sink.events();
+    assert_that(&events).size().is(3);
+
+    assert_that(events[0].clone()).contains("\"message\":\"Hello World! user=Jack\"");
+    assert_that(events[1].clone()).contains("\"message\":\"inner closed user=Jack\"");
+    assert_that(events[2].clone()).contains("\"message\":\"outer closed user=Jack\"");
+}
+
+#[test]
+fn summaries_have_the_fields_of_the_span_without_inheritance() {
+    let sink = ObservableSink::default();
+    let subscriber = tracing_subscriber::registry().with(
+        DatadogFormattingLayer::with_sink(sink.clone())
+            .with_span_field_inheritance(SpanFieldInheritance::Disabled)
+            .with_span_summaries(SpanSummaries::default()),
+    );
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    info_span!("outer", user = "Jack").in_scope(|| {
+        info_span!("inner", request = 42).in_scope(|| {});
+    });
+
+    let events = sink.events();
+    assert_that(&events).size().is(2);
+
+    assert_that(events[0].clone())
+        .contains("\"fields.request\":\"42\",\"message\":\"inner closed request=42\"");
+    assert_that(events[0].contains("user")).is(false);
+    assert_that(events[1].clone())
+        .contains("\"fields.user\":\"Jack\",\"message\":\"outer closed user=Jack\"");
+}
+
+#[test]
+fn short_spans_are_not_summarized() {
+    let (sink, _guard) = setup_summary_subscriber(
+        SpanSummaries::default().with_min_duration(Duration::from_millis(10)),
+    );
+
+    info_span!("short").in_scope(|| {});
+    info_span!("long").in_scope(|| sleep(Duration::from_millis(10)));
+
+    let events = sink.events();
+    assert_that(&events).size().is(1);
+
+    assert_that(events[0].clone()).contains("\"message\":\"long closed\"");
+}
+
+#[test]
+fn spans_are_not_summarized_by_default() {
+    let sink = ObservableSink::default();
+    let subscriber =
+        tracing_subscriber::registry().with(DatadogFormattingLayer::with_sink(sink.clone()));
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    info_span!("span").in_scope(|| {});
+
+    assert_that(sink.events()).is_empty();
+}
+
+#[test]
+fn summaries_are_correlated_with_the_span() {
+    let sink = ObservableSink::default();
+    let subscriber = tracing_subscriber::registry().with(
+        DatadogFormattingLayer::with_sink(sink.clone())
+            .with_correlation(NativeCorrelation::new())
+            .with_span_summaries(SpanSummaries::default()),
+    );
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    info_span!("span").in_scope(|| info!("Hello World!"));
+
+    let events = sink.events();
+    assert_that(&events).size().is(2);
+
+    let ids = |event: &str| {
+        let log: Value = serde_json::from_str(event).unwrap();
+        (log["dd.trace_id"].as_u64(), log["dd.span_id"].as_u64())
+    };
+
+    assert_that(ids(&events[0]).0).is_some();
+    assert_that(ids(&events[1])).is(ids(&events[0]));
+}
+
+fn setup_summary_subscriber(span_summaries: SpanSummaries) -> (ObservableSink, DefaultGuard) {
+    let sink = ObservableSink::default();
+
+    let subscriber = tracing_subscriber::registry()
+        .with(DatadogFormattingLayer::with_sink(sink.clone()).with_span_summaries(span_summaries));
+
+    let guard = tracing::subscriber::set_default(subscriber);
+
+    (sink, guard)
+}